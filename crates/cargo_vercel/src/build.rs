@@ -0,0 +1,334 @@
+//! `cargo vercel build`: compiles the functions and writes a
+//! [Build Output API](https://vercel.com/docs/build-output-api/v3) directory to
+//! `.vercel/output`, ready for `vercel deploy --prebuilt`.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::project::Project;
+use crate::Error;
+
+/// Output directory, relative to the project root.
+pub const OUTPUT_DIR: &str = ".vercel/output";
+/// Runtime of the functions, which run the `bootstrap` executable.
+const RUNTIME: &str = "provided.al2023";
+const BOOTSTRAP: &str = "bootstrap";
+
+#[derive(Debug, Default)]
+pub struct Options {
+    /// Builds without `--release`
+    pub debug: bool,
+    /// Target triple passed to `cargo build --target`
+    pub target: Option<String>,
+}
+
+/// A function of the output, served at `path`.
+#[derive(Debug)]
+pub struct Function<'a> {
+    /// Route path, e.g. `api/users/[id]`
+    pub path: String,
+    /// File whose `vercel.json` configuration applies, e.g. `api/users/[id].rs`
+    pub file: &'a str,
+    pub executable: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    reason: String,
+    target: Option<MessageTarget>,
+    executable: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct MessageTarget {
+    name: String,
+}
+
+/// Builds `project` and returns the path to its output directory.
+pub fn build(project: &Project, options: &Options) -> Result<PathBuf, Error> {
+    if let Some((first, second)) = project.router().conflicts().first() {
+        return Err(format!(
+            "{} and {} are both served at /{}, remove one of them",
+            first.module_file, second.module_file, first.path
+        )
+        .into());
+    }
+
+    let mut bins = vec![];
+    for file in project.entrypoints() {
+        let bin = project.bin(file).ok_or_else(|| {
+            format!(
+                "no [[bin]] target builds {}, run `cargo vercel check --fix` to add one",
+                file
+            )
+        })?;
+        bins.push(bin.name.as_str());
+    }
+
+    let executables = compile(project, &bins, options)?;
+    let functions = functions(project, &executables)?;
+    write_output(project, &functions)
+}
+
+/// Runs `cargo build` for `bins`, returning their executables by name.
+fn compile(
+    project: &Project,
+    bins: &[&str],
+    options: &Options,
+) -> Result<HashMap<String, PathBuf>, Error> {
+    let mut command = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()));
+    command
+        .args([
+            "build",
+            "--message-format=json-render-diagnostics",
+            "--manifest-path",
+        ])
+        .arg(project.root.join("Cargo.toml"))
+        .stdout(Stdio::piped());
+    if !options.debug {
+        command.arg("--release");
+    }
+    if let Some(ref target) = options.target {
+        command.args(["--target", target]);
+    }
+    for bin in bins {
+        command.args(["--bin", bin]);
+    }
+
+    let mut child = command.spawn()?;
+    let mut executables = HashMap::new();
+    let stdout = child.stdout.take().expect("stdout is piped");
+    for line in std::io::BufReader::new(stdout).lines() {
+        let Ok(message) = serde_json::from_str::<Message>(&line?) else {
+            continue;
+        };
+        if message.reason != "compiler-artifact" {
+            continue;
+        }
+        if let (Some(target), Some(executable)) = (message.target, message.executable) {
+            executables.insert(target.name, executable);
+        }
+    }
+
+    if !child.wait()?.success() {
+        return Err("`cargo build` failed".into());
+    }
+    Ok(executables)
+}
+
+/// The functions serving each route. In bundled mode, every route is served by the
+/// entrypoint's executable.
+pub fn functions<'a>(
+    project: &'a Project,
+    executables: &HashMap<String, PathBuf>,
+) -> Result<Vec<Function<'a>>, Error> {
+    project
+        .router()
+        .routes
+        .into_iter()
+        .map(|route| {
+            let file = match project.bundled {
+                Some(ref bundled) => bundled.entry.as_str(),
+                None => project
+                    .files
+                    .iter()
+                    .find(|f| **f == route.module_file)
+                    .expect("routes are built from the project's files"),
+            };
+            let executable = project
+                .bin(file)
+                .and_then(|bin| executables.get(&bin.name))
+                .ok_or_else(|| format!("no executable was built for {}", file))?;
+
+            Ok(Function {
+                path: route.path,
+                file,
+                executable: executable.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Replaces the project's output directory with `functions` and the routes' `config.json`.
+pub fn write_output(project: &Project, functions: &[Function]) -> Result<PathBuf, Error> {
+    let output = project.root.join(OUTPUT_DIR);
+    if output.exists() {
+        std::fs::remove_dir_all(&output)?;
+    }
+    std::fs::create_dir_all(&output)?;
+
+    // Functions sharing an executable hard link the first copy
+    let mut bootstraps = HashMap::<&Path, PathBuf>::new();
+    for function in functions {
+        let dir = output.join(format!("functions/{}.func", function.path));
+        std::fs::create_dir_all(&dir)?;
+
+        let bootstrap = dir.join(BOOTSTRAP);
+        match bootstraps.get(function.executable.as_path()) {
+            Some(first) if std::fs::hard_link(first, &bootstrap).is_ok() => {}
+            _ => {
+                std::fs::copy(&function.executable, &bootstrap)?;
+                set_executable(&bootstrap)?;
+                bootstraps.insert(function.executable.as_path(), bootstrap);
+            }
+        }
+
+        let config = project.config.function(function.file);
+        for file in config.include_files(&project.root)? {
+            let destination = dir.join(&file);
+            if let Some(parent) = destination.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::copy(project.root.join(&file), destination)?;
+        }
+
+        let mut vc_config = serde_json::json!({
+            "runtime": RUNTIME,
+            "handler": BOOTSTRAP,
+            "architecture": architecture(&function.executable),
+        });
+        if let Some(memory) = config.memory {
+            vc_config["memory"] = memory.into();
+        }
+        if let Some(max_duration) = config.max_duration {
+            vc_config["maxDuration"] = max_duration.into();
+        }
+        std::fs::write(
+            dir.join(".vc-config.json"),
+            serde_json::to_string_pretty(&vc_config)?,
+        )?;
+    }
+
+    std::fs::write(
+        output.join("config.json"),
+        serde_json::to_string_pretty(&project.router().build_output_config())?,
+    )?;
+    Ok(output)
+}
+
+/// Lambda architecture of `executable`, from the target directory it was built in, e.g.
+/// `target/aarch64-unknown-linux-musl/release`, or the host's.
+fn architecture(executable: &Path) -> &'static str {
+    let target = executable
+        .iter()
+        .filter_map(|c| c.to_str())
+        .find_map(|c| c.split_once("-unknown-linux-").map(|(arch, _)| arch));
+
+    match target.unwrap_or(std::env::consts::ARCH) {
+        "aarch64" => "arm64",
+        _ => "x86_64",
+    }
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{architecture, functions, write_output};
+    use crate::project::tests::{cleanup, project};
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    fn read_json(path: &Path) -> serde_json::Value {
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn it_writes_a_function_per_route() {
+        let project = project(
+            "build",
+            &[
+                ("api/foo.rs", ""),
+                ("api/users/[id].rs", ""),
+                ("static/logo.svg", "<svg/>"),
+                ("target/release/foo", "foo"),
+                ("target/release/user", "user"),
+                (
+                    "vercel.json",
+                    r#"{ "functions": { "api/users/*.rs": { "includeFiles": "static/*.{svg,png}", "memory": 512 } } }"#,
+                ),
+            ],
+            &[("foo", "api/foo.rs"), ("user", "api/users/[id].rs")],
+        );
+        let executables = HashMap::from([
+            ("foo".to_string(), project.root.join("target/release/foo")),
+            ("user".to_string(), project.root.join("target/release/user")),
+        ]);
+
+        let functions = functions(&project, &executables).unwrap();
+        let output = write_output(&project, &functions).unwrap();
+
+        let user = output.join("functions/api/users/[id].func");
+        assert_eq!(
+            std::fs::read_to_string(user.join("bootstrap")).unwrap(),
+            "user"
+        );
+        assert!(user.join("static/logo.svg").exists());
+        let vc_config = read_json(&user.join(".vc-config.json"));
+        assert_eq!(vc_config["runtime"], "provided.al2023");
+        assert_eq!(vc_config["handler"], "bootstrap");
+        assert_eq!(vc_config["memory"], 512);
+
+        let foo = output.join("functions/api/foo.func");
+        assert_eq!(
+            std::fs::read_to_string(foo.join("bootstrap")).unwrap(),
+            "foo"
+        );
+        assert!(!foo.join("static").exists());
+        assert!(read_json(&foo.join(".vc-config.json"))["memory"].is_null());
+
+        let config = read_json(&output.join("config.json"));
+        assert_eq!(config["version"], 3);
+        assert_eq!(config["routes"][1]["dest"], "/api/users/[id]?id=$id");
+
+        cleanup(&project.root);
+    }
+
+    #[test]
+    fn it_serves_all_bundled_routes_with_the_entrypoint() {
+        let project = project(
+            "build-bundled",
+            &[
+                ("api/main.rs", "#[bundled_api]\npub async fn handler() {}"),
+                ("api/foo.rs", ""),
+                ("api/[...all].rs", ""),
+                ("target/release/main", "main"),
+            ],
+            &[("main", "api/main.rs")],
+        );
+        let executables =
+            HashMap::from([("main".to_string(), project.root.join("target/release/main"))]);
+
+        let functions = functions(&project, &executables).unwrap();
+        assert_eq!(functions.len(), 2);
+        let output = write_output(&project, &functions).unwrap();
+
+        for path in ["api/foo", "api/[...all]"] {
+            let bootstrap = output.join(format!("functions/{}.func/bootstrap", path));
+            assert_eq!(std::fs::read_to_string(bootstrap).unwrap(), "main");
+        }
+        assert!(!output.join("functions/api/main.func").exists());
+
+        cleanup(&project.root);
+    }
+
+    #[test]
+    fn it_detects_the_architecture() {
+        let arm = Path::new("target/aarch64-unknown-linux-musl/release/foo");
+        assert_eq!(architecture(arm), "arm64");
+        let x86 = Path::new("target/x86_64-unknown-linux-gnu/release/foo");
+        assert_eq!(architecture(x86), "x86_64");
+    }
+}
@@ -0,0 +1,393 @@
+//! `cargo vercel check`: reports routes that `cargo vercel build` or the Node builder cannot
+//! build, because of a missing, duplicated or misplaced `[[bin]]` target.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+use crate::project::Project;
+use crate::Error;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// No `[[bin]]` builds the route, `name` is a free target name for it
+    MissingBin { file: String, name: String },
+    /// Several `[[bin]]`s are called `name`
+    DuplicateBin { name: String, paths: Vec<String> },
+    /// The `[[bin]]` called `name` points at a file that does not exist
+    MissingFile { name: String, path: String },
+    /// A route of the bundled handler in `entry` is also built as its own `[[bin]]`
+    BundledConflict {
+        file: String,
+        entry: String,
+        name: String,
+    },
+    /// Both files are served at `path`, e.g. `api/users.rs` and `api/users/index.rs`
+    RouteConflict {
+        path: String,
+        files: (String, String),
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::MissingBin { file, name } => write!(
+                f,
+                "{} has no [[bin]] target, add one with `name = \"{}\"` and `path = \"{}\"`",
+                file, name, file
+            ),
+            Problem::DuplicateBin { name, paths } => write!(
+                f,
+                "[[bin]] name `{}` is used by several targets: {}",
+                name,
+                paths.join(", ")
+            ),
+            Problem::MissingFile { name, path } => {
+                write!(f, "[[bin]] `{}` points at {}, which does not exist", name, path)
+            }
+            Problem::BundledConflict { file, entry, name } => write!(
+                f,
+                "{} is served by the bundled {} but is also built as [[bin]] `{}`, remove the target",
+                file, entry, name
+            ),
+            Problem::RouteConflict { path, files } => write!(
+                f,
+                "{} and {} are both served at /{}, remove one of them",
+                files.0, files.1, path
+            ),
+        }
+    }
+}
+
+/// The `[[bin]]` sections of `Cargo.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    bin: Vec<BinSection>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BinSection {
+    name: Option<String>,
+    path: Option<String>,
+}
+
+/// `[[bin]]` sections added by [`fix`].
+#[derive(Serialize)]
+struct NewBins<'a> {
+    bin: Vec<NewBin<'a>>,
+}
+
+#[derive(Serialize)]
+struct NewBin<'a> {
+    name: &'a str,
+    path: &'a str,
+}
+
+/// Checks the project in `root`.
+///
+/// Duplicate names and missing files are read from `Cargo.toml` directly, since `cargo
+/// metadata` fails on them. Routes are only checked once these are fixed.
+pub fn check(root: &Path) -> Result<Vec<Problem>, Error> {
+    let manifest = std::fs::read_to_string(root.join("Cargo.toml"))?;
+    let problems = manifest_problems(root, &manifest)?;
+    if !problems.is_empty() {
+        return Ok(problems);
+    }
+
+    Ok(route_problems(&Project::load(root)?))
+}
+
+fn manifest_problems(root: &Path, manifest: &str) -> Result<Vec<Problem>, Error> {
+    let manifest: Manifest = toml::from_str(manifest)?;
+
+    let mut paths = BTreeMap::<&str, Vec<String>>::new();
+    let mut problems = vec![];
+    for bin in &manifest.bin {
+        let Some(ref name) = bin.name else {
+            continue;
+        };
+        paths
+            .entry(name)
+            .or_default()
+            .push(bin.path.clone().unwrap_or_default());
+
+        if let Some(ref path) = bin.path {
+            if !root.join(path).is_file() {
+                problems.push(Problem::MissingFile {
+                    name: name.clone(),
+                    path: path.clone(),
+                });
+            }
+        }
+    }
+
+    let duplicates = paths
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(name, paths)| Problem::DuplicateBin {
+            name: name.to_string(),
+            paths,
+        });
+    problems.extend(duplicates);
+    Ok(problems)
+}
+
+fn route_problems(project: &Project) -> Vec<Problem> {
+    let mut names = project
+        .package
+        .targets
+        .iter()
+        .filter(|t| t.is_bin())
+        .map(|t| t.name.clone())
+        .collect::<HashSet<_>>();
+
+    let mut problems = project
+        .router()
+        .conflicts()
+        .into_iter()
+        .map(|(first, second)| Problem::RouteConflict {
+            path: first.path.clone(),
+            files: (first.module_file.clone(), second.module_file.clone()),
+        })
+        .collect::<Vec<_>>();
+    for file in project.entrypoints() {
+        if project.bin(file).is_none() {
+            let name = bin_name(file, &names);
+            names.insert(name.clone());
+            problems.push(Problem::MissingBin {
+                file: file.to_string(),
+                name,
+            });
+        }
+    }
+
+    if let Some(ref bundled) = project.bundled {
+        for file in project.files.iter().filter(|f| **f != bundled.entry) {
+            if let Some(bin) = project.bin(file) {
+                problems.push(Problem::BundledConflict {
+                    file: file.clone(),
+                    entry: bundled.entry.clone(),
+                    name: bin.name.clone(),
+                });
+            }
+        }
+    }
+    problems
+}
+
+/// A target name for `file` that is not in `taken`: its stem like the Node builder's
+/// `findBinaryName`, e.g. `_id_` for `api/user/[id].rs`, or its whole path, e.g. `user__id_`,
+/// followed by a number if that is taken too.
+fn bin_name(file: &str, taken: &HashSet<String>) -> String {
+    let mangle = |s: &str| s.replace(['[', ']', '.', '/'], "_");
+    let route = file.strip_suffix(".rs").unwrap_or(file);
+    let stem = route.rsplit('/').next().unwrap_or(route);
+
+    let name = mangle(stem);
+    if !taken.contains(&name) {
+        return name;
+    }
+    let name = mangle(route.strip_prefix("api/").unwrap_or(route));
+    if !taken.contains(&name) {
+        return name;
+    }
+    (2..)
+        .map(|i| format!("{}_{}", name, i))
+        .find(|name| !taken.contains(name))
+        .expect("a free target name")
+}
+
+/// Appends a `[[bin]]` section to the project's `Cargo.toml` for every missing target,
+/// returning how many were added.
+pub fn fix(root: &Path, problems: &[Problem]) -> Result<usize, Error> {
+    let bin = problems
+        .iter()
+        .filter_map(|problem| match problem {
+            Problem::MissingBin { file, name } => Some(NewBin { name, path: file }),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let added = bin.len();
+    if added == 0 {
+        return Ok(0);
+    }
+    let sections = toml::to_string(&NewBins { bin })?;
+
+    let path = root.join("Cargo.toml");
+    let manifest = std::fs::read_to_string(&path)?;
+    // Separate the sections from the previous ones by a blank line
+    let separator = match manifest.as_str() {
+        m if m.is_empty() || m.ends_with("\n\n") => "",
+        m if m.ends_with('\n') => "\n",
+        _ => "\n\n",
+    };
+    let mut file = std::fs::OpenOptions::new().append(true).open(&path)?;
+    write!(file, "{}{}", separator, sections)?;
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bin_name, fix, manifest_problems, route_problems, Problem};
+    use crate::project::tests::{cleanup, project};
+    use std::collections::HashSet;
+
+    #[test]
+    fn it_reports_duplicate_bins_and_missing_files() {
+        let project = project("check-manifest", &[("api/foo.rs", "")], &[]);
+        let manifest = r#"
+            [package]
+            name = "check-manifest"
+
+            [[bin]]
+            name = "foo"
+            path = "api/foo.rs"
+
+            [[bin]]
+            name = "foo"
+            path = "api/bar.rs"
+        "#;
+
+        let problems = manifest_problems(&project.root, manifest).unwrap();
+        assert_eq!(
+            problems,
+            [
+                Problem::MissingFile {
+                    name: "foo".to_string(),
+                    path: "api/bar.rs".to_string()
+                },
+                Problem::DuplicateBin {
+                    name: "foo".to_string(),
+                    paths: vec!["api/foo.rs".to_string(), "api/bar.rs".to_string()]
+                },
+            ]
+        );
+
+        cleanup(&project.root);
+    }
+
+    #[test]
+    fn it_adds_missing_bins() {
+        let project = project(
+            "check-routes",
+            &[
+                ("Cargo.toml", "[package]\nname = \"check-routes\""),
+                ("api/foo.rs", ""),
+                ("api/user/[id].rs", ""),
+                ("api/post/[id].rs", ""),
+            ],
+            &[("foo", "api/foo.rs")],
+        );
+
+        let problems = route_problems(&project);
+        assert_eq!(
+            problems,
+            [
+                Problem::MissingBin {
+                    file: "api/post/[id].rs".to_string(),
+                    name: "_id_".to_string()
+                },
+                Problem::MissingBin {
+                    file: "api/user/[id].rs".to_string(),
+                    name: "user__id_".to_string()
+                },
+            ]
+        );
+
+        assert_eq!(fix(&project.root, &problems).unwrap(), 2);
+        let manifest = std::fs::read_to_string(project.root.join("Cargo.toml")).unwrap();
+        let manifest: toml::Value = toml::from_str(&manifest).unwrap();
+        let bins = manifest["bin"].as_array().unwrap();
+        assert_eq!(bins[1]["name"].as_str(), Some("user__id_"));
+        assert_eq!(bins[1]["path"].as_str(), Some("api/user/[id].rs"));
+
+        cleanup(&project.root);
+    }
+
+    #[test]
+    fn it_picks_free_bin_names() {
+        let taken = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<HashSet<_>>();
+        assert_eq!(bin_name("api/user/[id].rs", &taken(&[])), "_id_");
+        assert_eq!(bin_name("api/user/[id].rs", &taken(&["_id_"])), "user__id_");
+        assert_eq!(
+            bin_name(
+                "api/user/[id].rs",
+                &taken(&["_id_", "user__id_", "user__id__2"])
+            ),
+            "user__id__3"
+        );
+    }
+
+    #[test]
+    fn it_escapes_added_bins() {
+        let project = project(
+            "check-escape",
+            &[("Cargo.toml", "[package]\nname = \"check-escape\"\n")],
+            &[],
+        );
+
+        let problems = [Problem::MissingBin {
+            file: r#"api/say "hi"\there.rs"#.to_string(),
+            name: "say".to_string(),
+        }];
+        assert_eq!(fix(&project.root, &problems).unwrap(), 1);
+        let manifest = std::fs::read_to_string(project.root.join("Cargo.toml")).unwrap();
+        let manifest: toml::Value = toml::from_str(&manifest).unwrap();
+        assert_eq!(
+            manifest["bin"][0]["path"].as_str(),
+            Some(r#"api/say "hi"\there.rs"#)
+        );
+
+        cleanup(&project.root);
+    }
+
+    #[test]
+    fn it_reports_routes_served_at_the_same_path() {
+        let project = project(
+            "check-conflicts",
+            &[("api/users.rs", ""), ("api/users/index.rs", "")],
+            &[
+                ("users", "api/users.rs"),
+                ("users_index", "api/users/index.rs"),
+            ],
+        );
+
+        assert_eq!(
+            route_problems(&project),
+            [Problem::RouteConflict {
+                path: "api/users".to_string(),
+                files: ("api/users.rs".to_string(), "api/users/index.rs".to_string())
+            }]
+        );
+
+        cleanup(&project.root);
+    }
+
+    #[test]
+    fn it_reports_bins_of_bundled_routes() {
+        let project = project(
+            "check-bundled",
+            &[
+                ("api/main.rs", "#[bundled_api]\npub async fn handler() {}"),
+                ("api/foo.rs", ""),
+                ("api/bar.rs", ""),
+            ],
+            &[("main", "api/main.rs"), ("foo", "api/foo.rs")],
+        );
+
+        assert_eq!(
+            route_problems(&project),
+            [Problem::BundledConflict {
+                file: "api/foo.rs".to_string(),
+                entry: "api/main.rs".to_string(),
+                name: "foo".to_string()
+            }]
+        );
+
+        cleanup(&project.root);
+    }
+}
@@ -0,0 +1,187 @@
+//! The `functions` section of `vercel.json`.
+
+use glob::{MatchOptions, Pattern};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct VercelConfig {
+    #[serde(default)]
+    pub functions: BTreeMap<String, FunctionConfig>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionConfig {
+    pub include_files: Option<IncludeFiles>,
+    pub memory: Option<u32>,
+    pub max_duration: Option<u32>,
+}
+
+/// A glob, or a list of globs, of files to add to the function.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum IncludeFiles {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl VercelConfig {
+    /// Reads `vercel.json` in `root`, if there is one.
+    pub fn load(root: &Path) -> Result<Self, Error> {
+        let path = root.join("vercel.json");
+        if !path.exists() {
+            return Ok(VercelConfig::default());
+        }
+
+        let config = std::fs::read_to_string(&path)?;
+        serde_json::from_str(&config)
+            .map_err(|e| format!("invalid {}: {}", path.display(), e).into())
+    }
+
+    /// Configuration of the function built from `file`, e.g. `api/users/[id].rs`, merged from
+    /// all `functions` globs matching it. Brackets are matched literally, like in the file
+    /// names of dynamic routes.
+    pub fn function(&self, file: &str) -> FunctionConfig {
+        // Like `vercel.json`, `*` does not match across directories
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+
+        let mut config = FunctionConfig::default();
+        for (pattern, function) in &self.functions {
+            let matches = expand_braces(pattern)
+                .iter()
+                .filter_map(|p| Pattern::new(&literal_brackets(p)).ok())
+                .any(|p| p.matches_with(file, options));
+            if !matches {
+                continue;
+            }
+
+            if function.include_files.is_some() {
+                config.include_files.clone_from(&function.include_files);
+            }
+            config.memory = function.memory.or(config.memory);
+            config.max_duration = function.max_duration.or(config.max_duration);
+        }
+        config
+    }
+}
+
+impl FunctionConfig {
+    /// Files matching `includeFiles`, relative to `root`.
+    pub fn include_files(&self, root: &Path) -> Result<Vec<PathBuf>, Error> {
+        let patterns = match self.include_files {
+            Some(IncludeFiles::One(ref pattern)) => vec![pattern.clone()],
+            Some(IncludeFiles::Many(ref patterns)) => patterns.clone(),
+            None => vec![],
+        };
+
+        let mut files = vec![];
+        for pattern in patterns.iter().flat_map(|p| expand_braces(p)) {
+            let pattern = format!("{}/{}", Pattern::escape(&root.to_string_lossy()), pattern);
+            for path in glob::glob(&pattern)? {
+                let path = path?;
+                if path.is_file() {
+                    files.push(path.strip_prefix(root)?.to_path_buf());
+                }
+            }
+        }
+        files.sort();
+        files.dedup();
+        Ok(files)
+    }
+}
+
+/// Escapes the brackets of `pattern`, so `[id]` matches a dynamic route segment instead of
+/// one of the characters `i` and `d`.
+fn literal_brackets(pattern: &str) -> String {
+    let mut escaped = String::with_capacity(pattern.len());
+    for c in pattern.chars() {
+        match c {
+            '[' | ']' => escaped.extend(['[', c, ']']),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Expands `{a,b}` alternatives, which `glob` does not support, e.g. `*.{txt,svg}` into
+/// `*.txt` and `*.svg`.
+pub fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(start) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+    let Some(len) = pattern[start..].find('}') else {
+        return vec![pattern.to_string()];
+    };
+
+    let (prefix, rest) = pattern.split_at(start);
+    let alternatives = &rest[1..len];
+    let suffix = &rest[len + 1..];
+    alternatives
+        .split(',')
+        .flat_map(|alternative| expand_braces(&format!("{}{}{}", prefix, alternative, suffix)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand_braces, VercelConfig};
+
+    #[test]
+    fn it_expands_braces() {
+        assert_eq!(
+            expand_braces("static/**/*.{txt,svg}"),
+            ["static/**/*.txt", "static/**/*.svg"]
+        );
+        assert_eq!(expand_braces("{a,b}/{c,d}").len(), 4);
+        assert_eq!(expand_braces("api/**/*.rs"), ["api/**/*.rs"]);
+    }
+
+    #[test]
+    fn it_merges_matching_function_configs() {
+        let config: VercelConfig = serde_json::from_str(
+            r#"{
+                "functions": {
+                    "api/**/*.rs": { "includeFiles": "static/**", "memory": 512 },
+                    "api/users/*.rs": { "maxDuration": 30 }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let users = config.function("api/users/[id].rs");
+        assert!(users.include_files.is_some());
+        assert_eq!(users.memory, Some(512));
+        assert_eq!(users.max_duration, Some(30));
+
+        let nested = config.function("api/users/[id]/posts.rs");
+        assert_eq!(nested.max_duration, None);
+        assert!(config.function("src/lib.rs").memory.is_none());
+    }
+
+    #[test]
+    fn it_matches_bracketed_function_keys_literally() {
+        let config: VercelConfig = serde_json::from_str(
+            r#"{
+                "functions": {
+                    "api/users/[id].rs": { "memory": 1024 },
+                    "api/[[...slug]]/*.rs": { "maxDuration": 60 }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.function("api/users/[id].rs").memory, Some(1024));
+        assert_eq!(config.function("api/users/i.rs").memory, None);
+        assert_eq!(
+            config.function("api/[[...slug]]/index.rs").max_duration,
+            Some(60)
+        );
+    }
+}
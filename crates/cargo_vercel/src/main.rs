@@ -0,0 +1,154 @@
+//! `cargo vercel`, building Vercel Rust functions without the Node builder.
+//!
+//! ```shell
+//! cargo vercel build && vercel deploy --prebuilt
+//! ```
+
+mod build;
+mod check;
+mod config;
+mod project;
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use project::Project;
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+const USAGE: &str = "\
+Usage: cargo vercel <COMMAND> [OPTIONS]
+
+Commands:
+  build    Build the functions in `api` into `.vercel/output`
+  check    Check that every route is built by a `[[bin]]` target
+
+Options:
+  --cwd <DIR>        Directory of the project, defaults to the current one
+  --target <TRIPLE>  Build for the target triple
+  --debug            Build without `--release`
+  --fix              Add the missing `[[bin]]` targets to `Cargo.toml`
+  -h, --help         Print this help";
+
+#[derive(Debug, Default)]
+struct Args {
+    command: Option<String>,
+    cwd: Option<PathBuf>,
+    build: build::Options,
+    fix: bool,
+    help: bool,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, Error> {
+    let mut args = args.into_iter().peekable();
+    // `cargo vercel` runs `cargo-vercel vercel ...`
+    if args.peek().map(String::as_str) == Some("vercel") {
+        args.next();
+    }
+
+    let mut parsed = Args::default();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("{} requires a value", name))
+        };
+
+        match arg.as_str() {
+            "--cwd" => parsed.cwd = Some(value("--cwd")?.into()),
+            "--target" => parsed.build.target = Some(value("--target")?),
+            "--debug" => parsed.build.debug = true,
+            "--fix" => parsed.fix = true,
+            "-h" | "--help" => parsed.help = true,
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg).into()),
+            _ if parsed.command.is_none() => parsed.command = Some(arg),
+            _ => return Err(format!("unexpected argument {}", arg).into()),
+        }
+    }
+    Ok(parsed)
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    let root = args.cwd.unwrap_or_else(|| PathBuf::from("."));
+    match args.command.as_deref() {
+        Some("build") => {
+            let project = Project::load(&root)?;
+            eprintln!("Building {}", project.package.name);
+            let output = build::build(&project, &args.build)?;
+            eprintln!("Wrote {}", output.display());
+            Ok(())
+        }
+        Some("check") => {
+            let mut problems = check::check(&root)?;
+            if args.fix && !problems.is_empty() {
+                let added = check::fix(&root, &problems)?;
+                eprintln!("Added {} [[bin]] target(s) to Cargo.toml", added);
+                problems = check::check(&root)?;
+            }
+
+            for problem in &problems {
+                eprintln!("error: {}", problem);
+            }
+            match problems.len() {
+                0 => Ok(()),
+                n => Err(format!("found {} problem(s)", n).into()),
+            }
+        }
+        Some(command) => Err(format!("unknown command {}\n\n{}", command, USAGE).into()),
+        None => Err(USAGE.into()),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) if args.help => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_args;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn it_parses_args() {
+        let parsed = parse_args(args(&[
+            "vercel",
+            "build",
+            "--cwd",
+            "examples/simple",
+            "--target",
+            "x86_64-unknown-linux-musl",
+        ]))
+        .unwrap();
+        assert_eq!(parsed.command.as_deref(), Some("build"));
+        assert_eq!(parsed.cwd.unwrap().to_str(), Some("examples/simple"));
+        assert_eq!(
+            parsed.build.target.as_deref(),
+            Some("x86_64-unknown-linux-musl")
+        );
+        assert!(!parsed.build.debug);
+
+        assert!(parse_args(args(&["build", "--target"])).is_err());
+        assert!(parse_args(args(&["build", "--release"])).is_err());
+        assert!(parse_args(args(&["build", "check"])).is_err());
+    }
+}
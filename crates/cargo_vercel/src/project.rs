@@ -0,0 +1,393 @@
+//! The Cargo package holding the functions, and the routes in its `api` directory, or those
+//! found by its `bundled_api` handler.
+
+use glob::Pattern;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use vercel_runtime_router::bundled::{Args, Config};
+use vercel_runtime_router::{is_route_file, Router};
+
+use crate::config::VercelConfig;
+use crate::Error;
+
+/// Default entrypoint of bundled APIs, checked even if no `[[bin]]` builds it yet.
+pub const BUNDLED_ENTRYPOINT: &str = "api/main.rs";
+/// Middleware applied by `bundled_api`, which is not a route itself.
+const MIDDLEWARE_FILE: &str = "middleware.rs";
+/// Name of the attribute serving all routes from one handler.
+const BUNDLED_ATTRIBUTE: &str = "bundled_api";
+
+#[derive(Debug, Deserialize)]
+pub struct Metadata {
+    pub packages: Vec<Package>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub manifest_path: PathBuf,
+    pub targets: Vec<Target>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Target {
+    pub name: String,
+    pub kind: Vec<String>,
+    pub src_path: PathBuf,
+}
+
+impl Target {
+    pub fn is_bin(&self) -> bool {
+        self.kind.iter().any(|k| k == "bin")
+    }
+}
+
+impl Metadata {
+    /// Runs `cargo metadata` for the manifest in `root`.
+    pub fn load(root: &Path) -> Result<Self, Error> {
+        let output = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()))
+            .args([
+                "metadata",
+                "--format-version",
+                "1",
+                "--no-deps",
+                "--manifest-path",
+            ])
+            .arg(root.join("Cargo.toml"))
+            .output()?;
+        if !output.status.success() {
+            return Err(format!(
+                "`cargo metadata` failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        Ok(serde_json::from_slice(&output.stdout)?)
+    }
+}
+
+pub struct Project {
+    pub root: PathBuf,
+    pub package: Package,
+    pub config: VercelConfig,
+    /// Route files relative to `root`, e.g. `api/users/[id].rs`, without private modules
+    pub files: Vec<String>,
+    /// The handler serving all routes with `bundled_api`, if there is one
+    pub bundled: Option<Bundled>,
+}
+
+/// A `bundled_api` handler and the routes it serves.
+pub struct Bundled {
+    /// File holding the handler relative to the project root, e.g. `api/main.rs`
+    pub entry: String,
+    /// Routes as the handler's router sees them, below its base path, e.g. `api/users/[id].rs`
+    pub routes: Vec<String>,
+}
+
+impl Project {
+    pub fn load(root: &Path) -> Result<Self, Error> {
+        let root = root.canonicalize()?;
+        let metadata = Metadata::load(&root)?;
+        let manifest = root.join("Cargo.toml");
+        let package = metadata
+            .packages
+            .into_iter()
+            .find(|p| p.manifest_path == manifest)
+            .ok_or_else(|| format!("{} does not define a package", manifest.display()))?;
+
+        Self::new(root, package)
+    }
+
+    pub fn new(root: PathBuf, package: Package) -> Result<Self, Error> {
+        let bundled = find_bundled(&root, &package)?;
+        let mut files = vec![];
+        match bundled {
+            Some((_, ref config)) => {
+                for file in config.files().iter().filter(|f| is_route_file(f)) {
+                    let path = config.path(file);
+                    let path = path.strip_prefix(&root)?;
+                    files.push(path.to_string_lossy().replace('\\', "/"));
+                }
+            }
+            None => {
+                let pattern = format!("{}/api/**/*.rs", Pattern::escape(&root.to_string_lossy()));
+                for path in glob::glob(&pattern)? {
+                    let path = path?;
+                    let file = path
+                        .strip_prefix(&root)?
+                        .to_string_lossy()
+                        .replace('\\', "/");
+                    if is_route_file(&file) {
+                        files.push(file);
+                    }
+                }
+            }
+        }
+        files.sort();
+
+        let bundled = bundled.map(|(entry, config)| Bundled {
+            entry,
+            routes: config
+                .files()
+                .iter()
+                .filter(|f| is_route_file(f) && !is_middleware(f))
+                .map(|f| config.route_file(f))
+                .collect(),
+        });
+
+        Ok(Project {
+            config: VercelConfig::load(&root)?,
+            root,
+            package,
+            files,
+            bundled,
+        })
+    }
+
+    /// The routes served by the project: those of the bundled handler, otherwise all files.
+    pub fn router(&self) -> Router {
+        match self.bundled {
+            Some(ref bundled) => Router::from(
+                bundled
+                    .routes
+                    .iter()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>(),
+            ),
+            None => Router::from(self.files.iter().map(String::as_str).collect::<Vec<_>>()),
+        }
+    }
+
+    /// The files built into their own function: the bundled handler's, otherwise every route.
+    pub fn entrypoints(&self) -> Vec<&str> {
+        match self.bundled {
+            Some(ref bundled) => vec![bundled.entry.as_str()],
+            None => self.files.iter().map(String::as_str).collect(),
+        }
+    }
+
+    /// The `[[bin]]` target built from `file`.
+    pub fn bin(&self, file: &str) -> Option<&Target> {
+        let path = self.root.join(file);
+        self.package
+            .targets
+            .iter()
+            .filter(|t| t.is_bin())
+            .find(|t| t.src_path == path)
+    }
+}
+
+fn is_middleware(file: &str) -> bool {
+    file.rsplit('/').next() == Some(MIDDLEWARE_FILE)
+}
+
+/// The file of the `bundled_api` handler, relative to `root`, with the configuration of its
+/// arguments. Looks at the `[[bin]]` targets and the default entrypoint.
+fn find_bundled(root: &Path, package: &Package) -> Result<Option<(String, Config)>, Error> {
+    let mut candidates = package
+        .targets
+        .iter()
+        .filter(|t| t.is_bin())
+        .map(|t| t.src_path.clone())
+        .collect::<Vec<_>>();
+    candidates.push(root.join(BUNDLED_ENTRYPOINT));
+
+    for path in candidates {
+        let Ok(source) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let Some(args) = bundled_args(&source).map_err(|e| {
+            format!(
+                "invalid `{}` in {}: {}",
+                BUNDLED_ATTRIBUTE,
+                path.display(),
+                e
+            )
+        })?
+        else {
+            continue;
+        };
+        let config = args.resolve(root, root).map_err(|e| {
+            format!(
+                "invalid `{}` in {}: {}",
+                BUNDLED_ATTRIBUTE,
+                path.display(),
+                e
+            )
+        })?;
+        let entry = path.strip_prefix(root).unwrap_or(&path);
+        return Ok(Some((entry.to_string_lossy().replace('\\', "/"), config)));
+    }
+    Ok(None)
+}
+
+/// The arguments of the `bundled_api` attribute of a function in `source`, if there is one.
+/// Files which don't parse are not bundled handlers.
+fn bundled_args(source: &str) -> syn::Result<Option<Args>> {
+    let Ok(file) = syn::parse_file(source) else {
+        return Ok(None);
+    };
+    let attr = file
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            syn::Item::Fn(f) => Some(&f.attrs),
+            _ => None,
+        })
+        .flatten()
+        .find(|attr| {
+            attr.path()
+                .segments
+                .last()
+                .is_some_and(|s| s.ident == BUNDLED_ATTRIBUTE)
+        });
+
+    match attr.map(|attr| &attr.meta) {
+        Some(syn::Meta::List(list)) => list.parse_args().map(Some),
+        Some(_) => Ok(Some(Args::default())),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{Package, Project, Target};
+    use std::path::Path;
+
+    /// Creates a project in a temporary directory with `files`, and `[[bin]]`s for `bins`.
+    pub(crate) fn project(name: &str, files: &[(&str, &str)], bins: &[(&str, &str)]) -> Project {
+        let root =
+            std::env::temp_dir().join(format!("cargo-vercel-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for (file, content) in files {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        let root = root.canonicalize().unwrap();
+
+        let package = Package {
+            name: name.to_string(),
+            manifest_path: root.join("Cargo.toml"),
+            targets: bins
+                .iter()
+                .map(|(name, path)| Target {
+                    name: name.to_string(),
+                    kind: vec!["bin".to_string()],
+                    src_path: root.join(path),
+                })
+                .collect(),
+        };
+        Project::new(root, package).unwrap()
+    }
+
+    pub(crate) fn cleanup(root: &Path) {
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn it_discovers_bundled_routes() {
+        let project = project(
+            "bundled",
+            &[
+                ("api/main.rs", "#[bundled_api]\npub async fn handler() {}"),
+                ("api/foo.rs", ""),
+                ("api/users/[id].rs", ""),
+                ("api/users/middleware.rs", ""),
+                ("api/users/_validation.rs", ""),
+            ],
+            &[("main", "api/main.rs")],
+        );
+
+        assert!(project.bundled.is_some());
+        assert_eq!(project.entrypoints(), ["api/main.rs"]);
+        let routes = project
+            .router()
+            .routes
+            .iter()
+            .map(|r| r.path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(routes, ["api/foo", "api/users/[id]"]);
+        assert_eq!(project.bin("api/main.rs").unwrap().name, "main");
+        assert!(project.bin("api/foo.rs").is_none());
+
+        cleanup(&project.root);
+    }
+
+    #[test]
+    fn it_honours_bundled_api_arguments() {
+        let project = project(
+            "bundled-args",
+            &[
+                (
+                    "src/main.rs",
+                    "#[vercel_runtime::bundled_api(dir = \"src/routes\", base_path = \"/v1.0\", \
+                     entry = \"src/main.rs\", exclude = \"legacy/**\")]\n\
+                     pub async fn handler() {}",
+                ),
+                ("src/routes/users/[id].rs", ""),
+                ("src/routes/legacy/old.rs", ""),
+                ("src/routes/_db.rs", ""),
+                ("api/stale.rs", ""),
+            ],
+            &[("server", "src/main.rs")],
+        );
+
+        assert_eq!(project.entrypoints(), ["src/main.rs"]);
+        assert_eq!(project.files, ["src/routes/users/[id].rs"]);
+        let routes = project
+            .router()
+            .routes
+            .iter()
+            .map(|r| r.path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(routes, ["v1.0/users/[id]"]);
+
+        cleanup(&project.root);
+    }
+
+    #[test]
+    fn it_reports_invalid_bundled_api_arguments() {
+        let root =
+            std::env::temp_dir().join(format!("cargo-vercel-invalid-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("api")).unwrap();
+        std::fs::write(
+            root.join("api/main.rs"),
+            "#[bundled_api(dir = \"routes\")]\npub async fn handler() {}",
+        )
+        .unwrap();
+        let package = Package {
+            name: "invalid".to_string(),
+            manifest_path: root.join("Cargo.toml"),
+            targets: vec![],
+        };
+
+        let error = Project::new(root.clone(), package)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("`routes` is not a directory of this crate"));
+
+        cleanup(&root);
+    }
+
+    #[test]
+    fn it_treats_every_file_as_a_function_without_bundling() {
+        let project = project(
+            "unbundled",
+            &[("api/foo.rs", ""), ("api/bar/[id].rs", "")],
+            &[("foo", "api/foo.rs")],
+        );
+
+        assert!(project.bundled.is_none());
+        assert_eq!(project.entrypoints(), ["api/bar/[id].rs", "api/foo.rs"]);
+        assert_eq!(project.router().routes.len(), 2);
+        assert_eq!(
+            project.bin("api/foo.rs").map(|t| &t.src_path),
+            Some(&project.root.join("api/foo.rs"))
+        );
+
+        cleanup(&project.root);
+    }
+}
@@ -0,0 +1,144 @@
+//! The adapter of an axum version, which only differ in the axum crate and path syntax.
+
+/// Defines the adapter for the axum crate `$axum`, e.g. `axum07`, in the calling module.
+macro_rules! adapter {
+    ($axum:ident, $version:literal, $syntax:ident, $example:literal) => {
+        use $axum::routing::MethodRouter;
+        use $axum::Router;
+        use tower::Layer;
+        use vercel_runtime::limits::{BodyLimitLayer, TimeoutLayer};
+        use vercel_runtime::logging::LogLayer;
+        use vercel_runtime::metrics::MetricsLayer;
+        use vercel_runtime::{run_service, Error, ServiceBuilder};
+        use vercel_runtime_router::{Route, Router as FileRouter};
+
+        use crate::routes::PathSyntax;
+
+        pub use $axum as axum;
+
+        #[doc = concat!(
+            "Vercel service wrapping an axum ", $version, " service, created by [`VercelLayer`]."
+        )]
+        pub type VercelService<S> = crate::service::VercelService<S, $axum::body::Body>;
+
+        #[derive(Clone, Copy)]
+        pub struct VercelLayer;
+
+        impl<S> Layer<S> for VercelLayer {
+            type Service = VercelService<S>;
+
+            fn layer(&self, inner: S) -> Self::Service {
+                VercelService::new(inner)
+            }
+        }
+
+        /// Runs an axum `Router` as a Vercel function. Use [`VercelApp`] for more options.
+        pub async fn run(app: Router) -> Result<(), Error> {
+            VercelApp::new(app).run().await
+        }
+
+        /// An axum `Router` prepared to be served as a Vercel function.
+        pub struct VercelApp {
+            router: Router,
+            base_path: Option<String>,
+            metrics: MetricsLayer,
+        }
+
+        impl VercelApp {
+            pub fn new(router: Router) -> Self {
+                VercelApp {
+                    router,
+                    base_path: None,
+                    metrics: MetricsLayer::new(),
+                }
+            }
+
+            /// Serves the router below `base_path` (e.g. `/api`), so its routes can be declared
+            /// without the prefix of the function's path.
+            pub fn base_path(mut self, base_path: &str) -> Self {
+                self.base_path = crate::normalize_base_path(base_path);
+                self
+            }
+
+            /// Records invocation metrics with `metrics`, e.g. to add a `Server-Timing` header
+            /// or map request paths to the `route` label, which is left out by default.
+            pub fn metrics(mut self, metrics: MetricsLayer) -> Self {
+                self.metrics = metrics;
+                self
+            }
+
+            pub fn into_router(self) -> Router {
+                match self.base_path {
+                    Some(base_path) => Router::new().nest(&base_path, self.router),
+                    None => self.router,
+                }
+            }
+
+            pub async fn run(self) -> Result<(), Error> {
+                crate::init_tracing();
+
+                let metrics = self.metrics.clone();
+                let handler = ServiceBuilder::new()
+                    .layer(VercelLayer)
+                    .layer(LogLayer)
+                    .layer(metrics)
+                    .layer(BodyLimitLayer::from_env())
+                    .layer(TimeoutLayer::from_env())
+                    .service(self.into_router());
+
+                run_service(handler).await
+            }
+        }
+
+        #[doc = concat!(
+            "Returns the axum ", $version, " route paths serving a file based route, e.g. `",
+            $example, "` for `api/users/[id].rs`."
+        )]
+        pub fn axum_paths(route: &Route) -> Vec<String> {
+            crate::routes::axum_paths(route, PathSyntax::$syntax)
+        }
+
+        /// Mounts handlers on an axum `Router` by their Vercel file path, so `Path` extractors
+        /// receive the parameters named in the file name.
+        pub trait FileRoutes<S> {
+            /// Serves `method_router` at the route of `module_file`, e.g. `api/users/[id].rs`.
+            fn file_route(self, module_file: &str, method_router: MethodRouter<S>) -> Self;
+
+            /// Serves every route of `routes` for which `f` returns a handler.
+            fn file_routes<F>(self, routes: &FileRouter, f: F) -> Self
+            where
+                F: FnMut(&Route) -> Option<MethodRouter<S>>;
+        }
+
+        impl<S> FileRoutes<S> for Router<S>
+        where
+            S: Clone + Send + Sync + 'static,
+        {
+            fn file_route(self, module_file: &str, method_router: MethodRouter<S>) -> Self {
+                mount(self, &Route::from(module_file), method_router)
+            }
+
+            fn file_routes<F>(self, routes: &FileRouter, mut f: F) -> Self
+            where
+                F: FnMut(&Route) -> Option<MethodRouter<S>>,
+            {
+                routes
+                    .routes
+                    .iter()
+                    .fold(self, |router, route| match f(route) {
+                        Some(method_router) => mount(router, route, method_router),
+                        None => router,
+                    })
+            }
+        }
+
+        fn mount<S>(router: Router<S>, route: &Route, method_router: MethodRouter<S>) -> Router<S>
+        where
+            S: Clone + Send + Sync + 'static,
+        {
+            axum_paths(route).iter().fold(router, |router, path| {
+                router.route(path, method_router.clone())
+            })
+        }
+    };
+}
@@ -0,0 +1,3 @@
+//! Adapter for axum 0.7, enabled by the `axum07` feature.
+
+adapter!(axum07, "0.7", Colon, "/api/users/:id");
@@ -0,0 +1,3 @@
+//! Adapter for axum 0.8, enabled by the `axum08` feature.
+
+adapter!(axum08, "0.8", Braces, "/api/users/{id}");
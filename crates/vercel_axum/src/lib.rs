@@ -1,90 +1,36 @@
-use axum::response::IntoResponse;
-use base64::prelude::*;
-use http_body_util::BodyExt;
-use std::{future::Future, pin::Pin};
-use tower::Layer;
-use tower_service::Service;
-
-use vercel_runtime::request::{Event, VercelRequest};
-use vercel_runtime::response::EventResponse;
-
-#[derive(Clone, Copy)]
-pub struct VercelLayer;
-
-impl<S> Layer<S> for VercelLayer {
-    type Service = VercelService<S>;
-
-    fn layer(&self, inner: S) -> Self::Service {
-        VercelService { inner }
-    }
-}
-
-pub struct VercelService<S> {
-    inner: S,
+//! Axum adapter for the Vercel Rust runtime.
+//!
+//! Each supported axum major version lives behind its own cargo feature (`axum07`, `axum08`)
+//! and module. The crate root re-exports the enabled version only if exactly one is enabled, so
+//! that enabling the other feature elsewhere in the dependency graph cannot switch it; crates
+//! that may be built with both should use the versioned modules.
+
+#[cfg(not(any(feature = "axum07", feature = "axum08")))]
+compile_error!("vercel_axum requires at least one of the `axum07` or `axum08` features");
+
+#[macro_use]
+mod adapter;
+mod routes;
+mod service;
+
+#[cfg(feature = "axum07")]
+pub mod axum07;
+#[cfg(feature = "axum08")]
+pub mod axum08;
+
+#[cfg(all(feature = "axum07", not(feature = "axum08")))]
+pub use axum07::{axum, axum_paths, run, FileRoutes, VercelApp, VercelLayer, VercelService};
+#[cfg(all(feature = "axum08", not(feature = "axum07")))]
+pub use axum08::{axum, axum_paths, run, FileRoutes, VercelApp, VercelLayer, VercelService};
+
+fn init_tracing() {
+    // Ignore the error so users may install their own subscriber before calling `run`
+    let _ = vercel_runtime::logging::try_init();
 }
 
-impl<S> Service<Event<'_>> for VercelService<S>
-where
-    S: Service<axum::http::Request<axum::body::Body>>,
-    S::Response: axum::response::IntoResponse + Send + 'static,
-    S::Error: std::error::Error + Send + Sync + 'static,
-    S::Future: Send + 'static,
-{
-    type Response = EventResponse;
-    type Error = vercel_runtime::Error;
-    type Future =
-        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
-
-    fn poll_ready(
-        &mut self,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Result<(), Self::Error>> {
-        self.inner.poll_ready(cx).map_err(Into::into)
-    }
-
-    fn call(&mut self, event: Event) -> Self::Future {
-        let (event, _context) = event.into_parts();
-        let request = serde_json::from_str::<VercelRequest>(&event.body).unwrap_or_default();
-
-        let mut builder = axum::http::request::Builder::new()
-            .method(request.method)
-            .uri(format!("https://{}{}", request.host, request.path));
-        for (key, value) in request.headers {
-            if let Some(k) = key {
-                builder = builder.header(k, value);
-            }
-        }
-
-        let request: axum::http::Request<axum::body::Body> = match (request.body, request.encoding)
-        {
-            (Some(b), Some(encoding)) if encoding == "base64" => {
-                let engine = base64::prelude::BASE64_STANDARD;
-                let body = axum::body::Body::from(engine.decode(b.as_ref()).unwrap_or_default());
-                builder.body(body).unwrap_or_default()
-            }
-            (Some(b), _) => builder.body(axum::body::Body::from(b)).unwrap_or_default(),
-            (None, _) => builder.body(axum::body::Body::default()).unwrap_or_default(),
-        };
-
-        let fut = self.inner.call(request);
-        let fut = async move {
-            let resp = fut.await?;
-            let (parts, body) = resp.into_response().into_parts();
-            let bytes = body.into_data_stream().collect().await?.to_bytes();
-            let bytes: &[u8] = &bytes;
-            let body = std::str::from_utf8(bytes).unwrap_or_default();
-            let body: Option<vercel_runtime::Body> = match body {
-                "" => None,
-                _ => Some(body.into()),
-            };
-            Ok(EventResponse {
-                status_code: parts.status.as_u16(),
-                body,
-                headers: parts.headers,
-                encoding: None,
-            })
-        };
-
-        Box::pin(fut)
+fn normalize_base_path(base_path: &str) -> Option<String> {
+    match base_path.trim_matches('/') {
+        "" => None,
+        p => Some(format!("/{}", p)),
     }
 }
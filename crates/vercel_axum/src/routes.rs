@@ -0,0 +1,147 @@
+use vercel_runtime_router::Route;
+
+/// Path parameter syntax of the targeted axum version.
+#[derive(Clone, Copy)]
+pub(crate) enum PathSyntax {
+    /// `/:id` and `/*rest` (axum 0.7)
+    #[cfg(feature = "axum07")]
+    Colon,
+    /// `/{id}` and `/{*rest}` (axum 0.8)
+    #[cfg(feature = "axum08")]
+    Braces,
+}
+
+impl PathSyntax {
+    fn param(self, name: &str) -> String {
+        match self {
+            #[cfg(feature = "axum07")]
+            PathSyntax::Colon => format!(":{}", name),
+            #[cfg(feature = "axum08")]
+            PathSyntax::Braces => format!("{{{}}}", name),
+        }
+    }
+
+    fn wildcard(self, name: &str) -> String {
+        match self {
+            #[cfg(feature = "axum07")]
+            PathSyntax::Colon => format!("*{}", name),
+            #[cfg(feature = "axum08")]
+            PathSyntax::Braces => format!("{{*{}}}", name),
+        }
+    }
+}
+
+/// Translates a file based route into the axum route paths serving it.
+///
+/// Optional catch-all routes yield two paths, one for the bare prefix and one for the wildcard.
+pub(crate) fn axum_paths(route: &Route, syntax: PathSyntax) -> Vec<String> {
+    let mut path = String::new();
+    let mut paths = vec![];
+
+    for segment in route.path.split('/').filter(|s| !s.is_empty()) {
+        if let Some(name) = segment
+            .strip_prefix("[[...")
+            .and_then(|s| s.strip_suffix("]]"))
+        {
+            paths.push(if path.is_empty() {
+                "/".to_string()
+            } else {
+                path.clone()
+            });
+            path = format!("{}/{}", path, syntax.wildcard(name));
+        } else if let Some(name) = segment
+            .strip_prefix("[...")
+            .and_then(|s| s.strip_suffix(']'))
+        {
+            path = format!("{}/{}", path, syntax.wildcard(name));
+        } else if let Some(param) = segment.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            // axum does not check constraints like `[id=u64]`, typed `Path` extractors do
+            let name = param.split_once('=').map_or(param, |(name, _)| name);
+            path = format!("{}/{}", path, syntax.param(name));
+        } else {
+            path = format!("{}/{}", path, segment);
+        }
+    }
+
+    if path.is_empty() {
+        path.push('/');
+    }
+
+    paths.push(path);
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{axum_paths, PathSyntax};
+    use vercel_runtime_router::Route;
+
+    #[test]
+    fn it_translates_static_route() {
+        let route = Route::from("api/users.rs");
+        #[cfg(feature = "axum07")]
+        assert_eq!(axum_paths(&route, PathSyntax::Colon), vec!["/api/users"]);
+        #[cfg(feature = "axum08")]
+        assert_eq!(axum_paths(&route, PathSyntax::Braces), vec!["/api/users"]);
+    }
+
+    #[test]
+    fn it_translates_dynamic_route() {
+        let route = Route::from("api/github/[owner]/[repo]/releases/[tag].rs");
+        #[cfg(feature = "axum07")]
+        assert_eq!(
+            axum_paths(&route, PathSyntax::Colon),
+            vec!["/api/github/:owner/:repo/releases/:tag"]
+        );
+        #[cfg(feature = "axum08")]
+        assert_eq!(
+            axum_paths(&route, PathSyntax::Braces),
+            vec!["/api/github/{owner}/{repo}/releases/{tag}"]
+        );
+    }
+
+    #[test]
+    fn it_translates_constrained_route() {
+        let route = Route::from("api/users/[id=u64]/posts.rs");
+        #[cfg(feature = "axum07")]
+        assert_eq!(
+            axum_paths(&route, PathSyntax::Colon),
+            vec!["/api/users/:id/posts"]
+        );
+        #[cfg(feature = "axum08")]
+        assert_eq!(
+            axum_paths(&route, PathSyntax::Braces),
+            vec!["/api/users/{id}/posts"]
+        );
+    }
+
+    #[test]
+    fn it_translates_catch_all_route() {
+        let route = Route::from("api/[id]/files/[...path].rs");
+        #[cfg(feature = "axum07")]
+        assert_eq!(
+            axum_paths(&route, PathSyntax::Colon),
+            vec!["/api/:id/files/*path"]
+        );
+        #[cfg(feature = "axum08")]
+        assert_eq!(
+            axum_paths(&route, PathSyntax::Braces),
+            vec!["/api/{id}/files/{*path}"]
+        );
+    }
+
+    #[test]
+    fn it_translates_optional_catch_all_route() {
+        let route = Route::from("api/optional/[[...slugs]].rs");
+        #[cfg(feature = "axum07")]
+        assert_eq!(
+            axum_paths(&route, PathSyntax::Colon),
+            vec!["/api/optional", "/api/optional/*slugs"]
+        );
+        #[cfg(feature = "axum08")]
+        assert_eq!(
+            axum_paths(&route, PathSyntax::Braces),
+            vec!["/api/optional", "/api/optional/{*slugs}"]
+        );
+    }
+}
@@ -0,0 +1,463 @@
+use base64::prelude::*;
+use bytes::Bytes;
+use http::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use http::StatusCode;
+use http_body_util::BodyExt;
+use serde_json::json;
+use std::{fmt, future::Future, marker::PhantomData, pin::Pin};
+use tower_service::Service;
+use tracing::error;
+
+use vercel_runtime::request::{Event, VercelRequest};
+use vercel_runtime::response::EventResponse;
+
+/// Tower service translating Vercel invocation events into `http` requests for an inner
+/// axum service. The body type `B` is picked by the versioned `VercelLayer`.
+pub struct VercelService<S, B> {
+    inner: S,
+    _body: PhantomData<fn() -> B>,
+}
+
+impl<S, B> VercelService<S, B> {
+    pub(crate) fn new(inner: S) -> Self {
+        VercelService {
+            inner,
+            _body: PhantomData,
+        }
+    }
+}
+
+impl<S: Clone, B> Clone for VercelService<S, B> {
+    fn clone(&self) -> Self {
+        VercelService::new(self.inner.clone())
+    }
+}
+
+impl<S, B, ResBody> Service<Event<'_>> for VercelService<S, B>
+where
+    S: Service<http::Request<B>, Response = http::Response<ResBody>>,
+    S::Error: std::error::Error + Send + Sync + 'static,
+    S::Future: Send + 'static,
+    B: From<Bytes>,
+    ResBody: http_body::Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: std::error::Error + Send + Sync + 'static,
+{
+    type Response = EventResponse;
+    type Error = vercel_runtime::Error;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, event: Event) -> Self::Future {
+        let request = match into_http_request(event) {
+            Ok(request) => request.map(B::from),
+            Err(e) => {
+                error!("Could not convert Vercel event into a request: {}", e);
+                let response = error_response(StatusCode::BAD_REQUEST, e.code(), &e.to_string());
+                return Box::pin(std::future::ready(Ok(response)));
+            }
+        };
+
+        let fut = self.inner.call(request);
+        let fut = async move {
+            let resp = match fut.await {
+                Ok(resp) => resp,
+                Err(e) => {
+                    error!("Inner service failed to handle request: {}", e);
+                    return Ok(error_response(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "internal_server_error",
+                        "Internal Server Error",
+                    ));
+                }
+            };
+
+            match into_event_response(resp).await {
+                Ok(response) => Ok(response),
+                Err(e) => {
+                    error!("Could not read response body: {}", e);
+                    Ok(error_response(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "internal_server_error",
+                        "Internal Server Error",
+                    ))
+                }
+            }
+        };
+
+        Box::pin(fut)
+    }
+}
+
+/// Reasons a Vercel invocation event cannot be turned into an `http::Request`.
+#[derive(Debug)]
+enum RequestError {
+    /// The event body is not a valid Vercel proxy request.
+    Deserialize(serde_json::Error),
+    /// The request body claims to be base64 encoded but is not.
+    Base64(base64::DecodeError),
+    /// The method, URI or headers were rejected by the request builder.
+    Build(http::Error),
+}
+
+impl RequestError {
+    fn code(&self) -> &'static str {
+        match self {
+            RequestError::Deserialize(_) => "invalid_event",
+            RequestError::Base64(_) => "invalid_body_encoding",
+            RequestError::Build(_) => "invalid_request",
+        }
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::Deserialize(e) => write!(f, "invalid Vercel request event: {}", e),
+            RequestError::Base64(e) => write!(f, "invalid base64 request body: {}", e),
+            RequestError::Build(e) => write!(f, "invalid request: {}", e),
+        }
+    }
+}
+
+/// Converts a Vercel invocation event into an `http::Request` carrying the decoded body.
+fn into_http_request(event: Event) -> Result<http::Request<Bytes>, RequestError> {
+    let (event, context) = event.into_parts();
+    let request =
+        serde_json::from_str::<VercelRequest>(&event.body).map_err(RequestError::Deserialize)?;
+
+    let mut builder = http::request::Builder::new()
+        .extension(context)
+        .method(request.method)
+        .uri(format!("https://{}{}", request.host, request.path));
+    for (key, value) in request.headers {
+        if let Some(k) = key {
+            builder = builder.header(k, value);
+        }
+    }
+
+    let body = match (request.body, request.encoding) {
+        (Some(b), Some(encoding)) if encoding == "base64" => {
+            let engine = base64::prelude::BASE64_STANDARD;
+            Bytes::from(engine.decode(b.as_ref()).map_err(RequestError::Base64)?)
+        }
+        (Some(b), _) => Bytes::from(b.into_owned()),
+        (None, _) => Bytes::new(),
+    };
+
+    builder.body(body).map_err(RequestError::Build)
+}
+
+/// Builds a JSON error response in the same shape as the `vercel_runtime::http` helpers.
+fn error_response(status: StatusCode, code: &str, message: &str) -> EventResponse {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+    EventResponse {
+        status_code: status.as_u16(),
+        headers,
+        body: Some(
+            json!({ "message": message, "code": code })
+                .to_string()
+                .into(),
+        ),
+        encoding: None,
+    }
+}
+
+/// Collects an `http::Response` into the `EventResponse` expected by the Vercel runtime.
+/// Bodies that are not valid UTF-8 are passed on as binary and sent base64 encoded.
+async fn into_event_response<B>(
+    resp: http::Response<B>,
+) -> Result<EventResponse, vercel_runtime::Error>
+where
+    B: http_body::Body<Data = Bytes>,
+    B::Error: std::error::Error + Send + Sync + 'static,
+{
+    let (parts, body) = resp.into_parts();
+    let bytes = body.collect().await?.to_bytes();
+    let body = match String::from_utf8(bytes.into()) {
+        Ok(text) if text.is_empty() => vercel_runtime::Body::Empty,
+        Ok(text) => vercel_runtime::Body::Text(text),
+        Err(e) => vercel_runtime::Body::Binary(e.into_bytes()),
+    };
+    Ok(http::Response::from_parts(parts, body).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use http_body_util::{BodyExt, Full};
+    use lambda_runtime::{Context, LambdaEvent};
+    use serde_json::json;
+    use std::{borrow::Cow, io};
+    use tower::{service_fn, Layer, Service, ServiceExt};
+    use vercel_runtime::request::VercelEvent;
+    use vercel_runtime::Body;
+
+    fn event(request: serde_json::Value) -> super::Event<'static> {
+        LambdaEvent::new(
+            VercelEvent {
+                action: Cow::Borrowed("Invoke"),
+                body: Cow::Owned(request.to_string()),
+            },
+            Context::default(),
+        )
+    }
+
+    fn text(body: Option<Body>) -> String {
+        match body {
+            Some(Body::Text(t)) => t,
+            other => panic!("expected text body, got {:?}", other),
+        }
+    }
+
+    fn error_code(response: &super::EventResponse) -> String {
+        let body: serde_json::Value = serde_json::from_str(&text(response.body.clone())).unwrap();
+        body["code"].as_str().unwrap().to_string()
+    }
+
+    fn echo() -> impl Service<
+        super::Event<'static>,
+        Response = super::EventResponse,
+        Error = vercel_runtime::Error,
+    > {
+        super::VercelService::<_, Full<Bytes>>::new(service_fn(
+            |req: http::Request<Full<Bytes>>| async move {
+                let body = req.into_body().collect().await.unwrap().to_bytes();
+                Ok::<_, io::Error>(http::Response::new(Full::new(body)))
+            },
+        ))
+    }
+
+    #[tokio::test]
+    async fn malformed_event_is_bad_request() {
+        let response = echo()
+            .oneshot(LambdaEvent::new(
+                VercelEvent {
+                    action: Cow::Borrowed("Invoke"),
+                    body: Cow::Borrowed("not json"),
+                },
+                Context::default(),
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status_code, 400);
+        assert_eq!(error_code(&response), "invalid_event");
+    }
+
+    #[tokio::test]
+    async fn invalid_base64_body_is_bad_request() {
+        let response = echo()
+            .oneshot(event(json!({
+                "host": "example.vercel.app",
+                "path": "/api/echo",
+                "method": "POST",
+                "headers": {},
+                "body": "not base64!",
+                "encoding": "base64"
+            })))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status_code, 400);
+        assert_eq!(error_code(&response), "invalid_body_encoding");
+    }
+
+    #[tokio::test]
+    async fn invalid_uri_is_bad_request() {
+        let response = echo()
+            .oneshot(event(json!({
+                "host": "example vercel app",
+                "path": "/api/echo",
+                "method": "GET",
+                "headers": {}
+            })))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status_code, 400);
+        assert_eq!(error_code(&response), "invalid_request");
+    }
+
+    #[tokio::test]
+    async fn inner_service_error_is_internal_server_error() {
+        let service = super::VercelService::<_, Full<Bytes>>::new(service_fn(
+            |_req: http::Request<Full<Bytes>>| async move {
+                Err::<http::Response<Full<Bytes>>, _>(io::Error::other("database unavailable"))
+            },
+        ));
+
+        let response = service
+            .oneshot(event(json!({
+                "host": "example.vercel.app",
+                "path": "/api/echo",
+                "method": "GET",
+                "headers": {}
+            })))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status_code, 500);
+        assert_eq!(error_code(&response), "internal_server_error");
+    }
+
+    #[tokio::test]
+    async fn binary_response_is_base64_encoded() {
+        let service = super::VercelService::<_, Full<Bytes>>::new(service_fn(
+            |_req: http::Request<Full<Bytes>>| async move {
+                let gzip_magic = Bytes::from_static(&[0x1f, 0x8b, 0x08, 0x00, 0xff]);
+                Ok::<_, io::Error>(http::Response::new(Full::new(gzip_magic)))
+            },
+        ));
+
+        let response = service
+            .oneshot(event(json!({
+                "host": "example.vercel.app",
+                "path": "/api/image",
+                "method": "GET",
+                "headers": {}
+            })))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.encoding.as_deref(), Some("base64"));
+        assert_eq!(
+            response.body,
+            Some(Body::Binary(vec![0x1f, 0x8b, 0x08, 0x00, 0xff]))
+        );
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["body"], "H4sIAP8=");
+        assert_eq!(json["encoding"], "base64");
+    }
+
+    macro_rules! adapter_tests {
+        ($module:ident) => {
+            mod $module {
+                use super::*;
+                use crate::$module::axum::extract::Path;
+                use crate::$module::axum::routing::{get, post};
+                use crate::$module::axum::Router;
+                use crate::$module::{FileRoutes, VercelApp, VercelLayer};
+
+                fn get_event(path: &str) -> super::super::Event<'static> {
+                    event(json!({
+                        "host": "example.vercel.app",
+                        "path": path,
+                        "method": "GET",
+                        "headers": {}
+                    }))
+                }
+
+                #[tokio::test]
+                async fn routes_request() {
+                    let app = Router::new().route("/api/hello", get(|| async { "hello" }));
+                    let service = VercelLayer.layer(app);
+
+                    let response = service
+                        .oneshot(event(json!({
+                            "host": "example.vercel.app",
+                            "path": "/api/hello",
+                            "method": "GET",
+                            "headers": {}
+                        })))
+                        .await
+                        .unwrap();
+
+                    assert_eq!(response.status_code, 200);
+                    assert_eq!(text(response.body), "hello");
+                }
+
+                #[tokio::test]
+                async fn decodes_base64_body() {
+                    let app = Router::new().route("/api/echo", post(|body: String| async { body }));
+                    let service = VercelLayer.layer(app);
+
+                    let response = service
+                        .oneshot(event(json!({
+                            "host": "example.vercel.app",
+                            "path": "/api/echo",
+                            "method": "POST",
+                            "headers": { "content-type": "text/plain" },
+                            "body": "aGVsbG8gd29ybGQ=",
+                            "encoding": "base64"
+                        })))
+                        .await
+                        .unwrap();
+
+                    assert_eq!(response.status_code, 200);
+                    assert_eq!(text(response.body), "hello world");
+                }
+
+                #[tokio::test]
+                async fn extracts_file_route_params() {
+                    let app = Router::new().file_route(
+                        "api/users/[id].rs",
+                        get(|Path(id): Path<String>| async move { id }),
+                    );
+
+                    let response = VercelLayer
+                        .layer(app)
+                        .oneshot(get_event("/api/users/42"))
+                        .await
+                        .unwrap();
+
+                    assert_eq!(text(response.body), "42");
+                }
+
+                #[tokio::test]
+                async fn serves_optional_catch_all_file_route() {
+                    let app = Router::new().file_route(
+                        "api/docs/[[...slug]].rs",
+                        get(|slug: Option<Path<String>>| async move {
+                            slug.map(|Path(slug)| slug).unwrap_or_else(|| "index".to_string())
+                        }),
+                    );
+
+                    let response = VercelLayer
+                        .layer(app.clone())
+                        .oneshot(get_event("/api/docs"))
+                        .await
+                        .unwrap();
+                    assert_eq!(text(response.body), "index");
+
+                    let response = VercelLayer
+                        .layer(app)
+                        .oneshot(get_event("/api/docs/guides/routing"))
+                        .await
+                        .unwrap();
+                    assert_eq!(text(response.body), "guides/routing");
+                }
+
+                #[tokio::test]
+                async fn strips_base_path() {
+                    let app = VercelApp::new(Router::new().route("/hello", get(|| async { "hello" })))
+                        .base_path("api/")
+                        .into_router();
+
+                    let response = VercelLayer
+                        .layer(app)
+                        .oneshot(get_event("/api/hello"))
+                        .await
+                        .unwrap();
+
+                    assert_eq!(response.status_code, 200);
+                    assert_eq!(text(response.body), "hello");
+                }
+            }
+        };
+    }
+
+    #[cfg(feature = "axum07")]
+    adapter_tests!(axum07);
+    #[cfg(feature = "axum08")]
+    adapter_tests!(axum08);
+}
@@ -0,0 +1,324 @@
+//! Cross-Origin Resource Sharing for Vercel functions.
+//!
+//! [`crate::run`] and [`crate::Runtime`] answer preflight requests and add CORS headers to
+//! responses when `CORS_ALLOWED_ORIGINS` is set, see [`CorsConfig::from_env`]. Use
+//! [`crate::Runtime::cors`] to configure it in code instead.
+
+use lambda_http::http::{HeaderName, HeaderValue, Method};
+use lambda_http::Error;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tower_http::cors::{AllowHeaders, AllowOrigin};
+
+pub use tower_http::cors::{Cors, CorsLayer};
+
+/// Comma separated origins, e.g. `https://example.com,https://*.example.com`, or `*`.
+pub const ALLOWED_ORIGINS_ENV: &str = "CORS_ALLOWED_ORIGINS";
+/// Comma separated methods, defaults to all common methods.
+pub const ALLOWED_METHODS_ENV: &str = "CORS_ALLOWED_METHODS";
+/// Comma separated request headers, defaults to the headers requested by the preflight.
+pub const ALLOWED_HEADERS_ENV: &str = "CORS_ALLOWED_HEADERS";
+/// `true` to allow credentials.
+pub const ALLOW_CREDENTIALS_ENV: &str = "CORS_ALLOW_CREDENTIALS";
+/// How long preflight responses may be cached, in seconds.
+pub const MAX_AGE_ENV: &str = "CORS_MAX_AGE";
+
+static FROM_ENV: OnceLock<Option<CorsLayer>> = OnceLock::new();
+
+/// Allowed origins, methods, headers and credentials, turned into a [`CorsLayer`] with
+/// [`CorsConfig::layer`].
+///
+/// ```
+/// use std::time::Duration;
+/// use vercel_runtime::cors::CorsConfig;
+///
+/// let cors = CorsConfig::new()
+///     .allow_origin("https://example.com")
+///     .allow_origin("https://*.example.com")
+///     .allow_credentials(true)
+///     .max_age(Duration::from_secs(3600))
+///     .layer();
+/// ```
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    origins: Vec<String>,
+    methods: Vec<Method>,
+    headers: Vec<HeaderName>,
+    credentials: bool,
+    max_age: Option<Duration>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CorsConfig {
+    /// Creates a configuration allowing no origins and all common methods.
+    pub fn new() -> Self {
+        CorsConfig {
+            origins: vec![],
+            methods: vec![
+                Method::GET,
+                Method::HEAD,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+                Method::OPTIONS,
+            ],
+            headers: vec![],
+            credentials: false,
+            max_age: None,
+        }
+    }
+
+    /// Reads the configuration from the `CORS_*` environment variables, returning `None` if
+    /// `CORS_ALLOWED_ORIGINS` is not set.
+    pub fn from_env() -> Result<Option<Self>, Error> {
+        let var = |name| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+        let list = |value: String| {
+            value
+                .split(',')
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .collect::<Vec<_>>()
+        };
+
+        let Some(origins) = var(ALLOWED_ORIGINS_ENV) else {
+            return Ok(None);
+        };
+
+        let mut config = CorsConfig::new();
+        config.origins = list(origins);
+        if let Some(methods) = var(ALLOWED_METHODS_ENV) {
+            config.methods = list(methods)
+                .iter()
+                .map(|m| Method::from_bytes(m.to_uppercase().as_bytes()))
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(headers) = var(ALLOWED_HEADERS_ENV) {
+            config.headers = list(headers)
+                .iter()
+                .map(|h| HeaderName::from_bytes(h.as_bytes()))
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(credentials) = var(ALLOW_CREDENTIALS_ENV) {
+            config.credentials = credentials.trim().parse()?;
+        }
+        if let Some(max_age) = var(MAX_AGE_ENV) {
+            config.max_age = Some(Duration::from_secs(max_age.trim().parse()?));
+        }
+
+        config.validate()?;
+        Ok(Some(config))
+    }
+
+    /// Allows an origin, `*` for any origin, or all subdomains of a domain by using a
+    /// wildcard, e.g. `https://*.example.com`.
+    pub fn allow_origin(mut self, origin: impl Into<String>) -> Self {
+        self.origins.push(origin.into());
+        self
+    }
+
+    /// Replaces the allowed methods.
+    pub fn allow_methods<I: IntoIterator<Item = Method>>(mut self, methods: I) -> Self {
+        self.methods = methods.into_iter().collect();
+        self
+    }
+
+    /// Allows request headers. Defaults to the headers requested by the preflight.
+    pub fn allow_headers<I: IntoIterator<Item = HeaderName>>(mut self, headers: I) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+
+    pub fn allow_credentials(mut self, credentials: bool) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// How long browsers may cache preflight responses.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Whether `origin` is allowed by this configuration.
+    pub fn is_allowed(&self, origin: &str) -> bool {
+        self.origins
+            .iter()
+            .any(|allowed| origin_matches(allowed, origin))
+    }
+
+    /// Rejects allowing credentials for any origin, which would let every site make
+    /// credentialed requests and read the responses.
+    fn validate(&self) -> Result<(), Error> {
+        match self.credentials && self.origins.iter().any(|o| o == "*") {
+            true => Err(
+                "CORS credentials cannot be allowed for any origin (`*`), list the \
+                 allowed origins instead"
+                    .into(),
+            ),
+            false => Ok(()),
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if credentials are allowed together with the `*` origin.
+    pub fn layer(&self) -> CorsLayer {
+        if let Err(e) = self.validate() {
+            panic!("{}", e);
+        }
+
+        let origin = match self.origins.iter().any(|o| o == "*") {
+            true => AllowOrigin::any(),
+            false => {
+                let config = self.clone();
+                AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+                    origin.to_str().is_ok_and(|o| config.is_allowed(o))
+                })
+            }
+        };
+
+        let headers = match self.headers.is_empty() {
+            true => AllowHeaders::mirror_request(),
+            false => AllowHeaders::list(self.headers.clone()),
+        };
+
+        let layer = CorsLayer::new()
+            .allow_origin(origin)
+            .allow_methods(self.methods.clone())
+            .allow_headers(headers)
+            .allow_credentials(self.credentials);
+
+        match self.max_age {
+            Some(max_age) => layer.max_age(max_age),
+            None => layer,
+        }
+    }
+}
+
+impl From<CorsConfig> for CorsLayer {
+    fn from(config: CorsConfig) -> Self {
+        config.layer()
+    }
+}
+
+fn origin_matches(allowed: &str, origin: &str) -> bool {
+    if allowed == "*" || allowed.eq_ignore_ascii_case(origin) {
+        return true;
+    }
+
+    match allowed.split_once("*.") {
+        Some((scheme, domain)) => origin
+            .strip_prefix(scheme)
+            .and_then(|rest| rest.strip_suffix(domain))
+            .and_then(|subdomain| subdomain.strip_suffix('.'))
+            .is_some_and(|subdomain| !subdomain.is_empty() && !subdomain.contains(['/', ':', '@'])),
+        None => false,
+    }
+}
+
+/// The layer configured by the `CORS_*` environment variables, if `CORS_ALLOWED_ORIGINS` is set.
+///
+/// Invalid values are logged and disable CORS handling.
+pub fn layer() -> Option<CorsLayer> {
+    FROM_ENV
+        .get_or_init(|| match CorsConfig::from_env() {
+            Ok(config) => config.map(|c| c.layer()),
+            Err(e) => {
+                tracing::error!("Invalid CORS configuration: {}", e);
+                None
+            }
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{origin_matches, CorsConfig};
+    use lambda_http::http::{Method, Request, Response, StatusCode};
+    use lambda_http::tower::Layer;
+    use lambda_http::{service_fn, Body, Error};
+    use std::time::Duration;
+    use tower_service::Service;
+
+    #[test]
+    fn it_matches_wildcard_subdomains() {
+        let allowed = "https://*.example.com";
+        assert!(origin_matches(allowed, "https://app.example.com"));
+        assert!(origin_matches(allowed, "https://a.b.example.com"));
+        assert!(!origin_matches(allowed, "https://example.com"));
+        assert!(!origin_matches(allowed, "http://app.example.com"));
+        assert!(!origin_matches(allowed, "https://app.example.com.evil.com"));
+        assert!(!origin_matches(allowed, "https://evilexample.com"));
+        assert!(origin_matches("*", "https://anything.dev"));
+        assert!(origin_matches("https://example.com", "https://example.com"));
+    }
+
+    #[test]
+    #[should_panic(expected = "credentials cannot be allowed for any origin")]
+    fn it_rejects_credentials_for_any_origin() {
+        let _ = CorsConfig::new()
+            .allow_origin("*")
+            .allow_credentials(true)
+            .layer();
+    }
+
+    #[test]
+    fn it_validates_credentials_for_any_origin() {
+        let config = CorsConfig::new().allow_origin("*").allow_credentials(true);
+        assert!(config.validate().is_err());
+        assert!(config.allow_credentials(false).validate().is_ok());
+    }
+
+    #[tokio::test]
+    async fn it_short_circuits_preflight_requests() {
+        let mut service = CorsConfig::new()
+            .allow_origin("https://*.example.com")
+            .allow_credentials(true)
+            .max_age(Duration::from_secs(600))
+            .layer()
+            .layer(service_fn(|_req: Request<Body>| async {
+                Ok::<_, Error>(
+                    Response::builder()
+                        .status(StatusCode::IM_A_TEAPOT)
+                        .body(Body::Empty)
+                        .unwrap(),
+                )
+            }));
+
+        let preflight = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("https://api.vercel.app/api/users")
+            .header("origin", "https://app.example.com")
+            .header("access-control-request-method", "POST")
+            .header("access-control-request-headers", "content-type")
+            .body(Body::Empty)
+            .unwrap();
+        let response = service.call(preflight).await.unwrap();
+        let headers = response.headers();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            headers["access-control-allow-origin"],
+            "https://app.example.com"
+        );
+        assert_eq!(headers["access-control-allow-credentials"], "true");
+        assert_eq!(headers["access-control-allow-headers"], "content-type");
+        assert_eq!(headers["access-control-max-age"], "600");
+
+        let request = Request::builder()
+            .uri("https://api.vercel.app/api/users")
+            .header("origin", "https://evil.com")
+            .body(Body::Empty)
+            .unwrap();
+        let response = service.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::IM_A_TEAPOT);
+        assert!(!response
+            .headers()
+            .contains_key("access-control-allow-origin"));
+    }
+}
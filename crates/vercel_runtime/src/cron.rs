@@ -0,0 +1,293 @@
+//! Handlers for [Vercel Cron Jobs](https://vercel.com/docs/cron-jobs).
+//!
+//! Vercel invokes the path configured in `vercel.json` with an `Authorization: Bearer
+//! <CRON_SECRET>` header. [`run_cron`] verifies it before calling the handler, answering `401`
+//! to any other request.
+//!
+//! ```no_run
+//! use vercel_runtime::cron::{run_cron, CronInvocation};
+//! use vercel_runtime::{Body, Error, Response};
+//!
+//! async fn handler(cron: CronInvocation) -> Result<Response<Body>, Error> {
+//!     tracing::info!("running job invoked at {:?}", cron.invoked_at);
+//!     Ok(Response::new(Body::Empty))
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Error> {
+//!     run_cron(handler).await
+//! }
+//! ```
+
+use lambda_http::http::{header, Response, StatusCode};
+use lambda_http::{Body, Error, Request};
+use pin_project_lite::pin_project;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{error, warn};
+
+use crate::{Runtime, State};
+
+/// Environment variable holding the secret Vercel sends with cron invocations.
+pub const CRON_SECRET_ENV: &str = "CRON_SECRET";
+
+/// A verified cron invocation.
+#[derive(Debug, Clone)]
+pub struct CronInvocation {
+    /// Path of the cron job as configured in `vercel.json`, e.g. `/api/cron`
+    pub path: String,
+    /// Percent-decoded query parameters of the configured path
+    pub query: HashMap<String, String>,
+    /// Start of the minute the invocation started in. Vercel doesn't send the scheduled time,
+    /// so this is a later minute than scheduled for invocations which start late.
+    pub invoked_at: SystemTime,
+}
+
+impl CronInvocation {
+    fn from_request(req: &Request) -> Self {
+        let query = req
+            .uri()
+            .query()
+            .map(|q| form_urlencoded::parse(q.as_bytes()).into_owned().collect())
+            .unwrap_or_default();
+
+        // Cron schedules have a granularity of one minute
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let invoked_at = UNIX_EPOCH + Duration::from_secs(now - now % 60);
+
+        CronInvocation {
+            path: req.uri().path().to_string(),
+            query,
+            invoked_at,
+        }
+    }
+}
+
+/// Why a cron invocation was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CronError {
+    /// `CRON_SECRET` is not set
+    SecretMissing,
+    /// The `Authorization` header is missing or does not carry the secret
+    Unauthorized,
+}
+
+impl std::fmt::Display for CronError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CronError::SecretMissing => write!(f, "{} is not set", CRON_SECRET_ENV),
+            CronError::Unauthorized => write!(f, "invalid cron secret"),
+        }
+    }
+}
+
+impl std::error::Error for CronError {}
+
+impl CronError {
+    /// JSON error response: `500` if the secret is not configured, `401` otherwise.
+    pub fn into_response(self) -> Response<Body> {
+        let (status, code) = match self {
+            CronError::SecretMissing => (StatusCode::INTERNAL_SERVER_ERROR, "cron_secret_missing"),
+            CronError::Unauthorized => (StatusCode::UNAUTHORIZED, "unauthorized"),
+        };
+        let body = serde_json::json!({ "message": self.to_string(), "code": code });
+
+        Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::Text(body.to_string()))
+            .expect("valid error response")
+    }
+}
+
+/// Verifies `req` carries the `CRON_SECRET`, compared in constant time.
+pub fn verify(req: &Request) -> Result<CronInvocation, CronError> {
+    let secret = std::env::var(CRON_SECRET_ENV).unwrap_or_default();
+    verify_secret(req, &secret)
+}
+
+/// Verifies `req` carries `secret`, compared in constant time. An empty `secret` is treated as
+/// missing.
+pub fn verify_secret(req: &Request, secret: &str) -> Result<CronInvocation, CronError> {
+    if secret.is_empty() {
+        error!("{} is not set, rejecting cron invocation", CRON_SECRET_ENV);
+        return Err(CronError::SecretMissing);
+    }
+
+    let authorized = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.as_bytes().strip_prefix(b"Bearer "))
+        .is_some_and(|token| constant_time_eq(token, secret.as_bytes()));
+
+    match authorized {
+        true => Ok(CronInvocation::from_request(req)),
+        false => {
+            warn!("Rejected cron invocation with an invalid secret");
+            Err(CronError::Unauthorized)
+        }
+    }
+}
+
+/// Compares `a` and `b` in time depending only on their lengths.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Function handling cron invocations, taking a [`CronInvocation`] and optionally the
+/// [`State`] set with [`Runtime::with_state`].
+pub trait CronHandler<S, M> {
+    type Future: Future<Output = Result<Response<Body>, Error>>;
+
+    fn call(&mut self, cron: CronInvocation, state: &State<S>) -> Self::Future;
+}
+
+impl<S, F, Fut> CronHandler<S, ()> for F
+where
+    F: FnMut(CronInvocation) -> Fut,
+    Fut: Future<Output = Result<Response<Body>, Error>>,
+{
+    type Future = Fut;
+
+    fn call(&mut self, cron: CronInvocation, _state: &State<S>) -> Self::Future {
+        self(cron)
+    }
+}
+
+impl<S, F, Fut> CronHandler<S, State<S>> for F
+where
+    F: FnMut(CronInvocation, State<S>) -> Fut,
+    Fut: Future<Output = Result<Response<Body>, Error>>,
+{
+    type Future = Fut;
+
+    fn call(&mut self, cron: CronInvocation, state: &State<S>) -> Self::Future {
+        self(cron, state.clone())
+    }
+}
+
+/// Verifies the `CRON_SECRET` of every invocation before calling `f`.
+pub async fn run_cron<H, M>(f: H) -> Result<(), Error>
+where
+    H: CronHandler<(), M>,
+{
+    Runtime::new().run_cron(f).await
+}
+
+impl<S> Runtime<S> {
+    /// Like [`Runtime::run`], verifying the `CRON_SECRET` of every invocation before calling
+    /// the cron handler `f`.
+    pub async fn run_cron<H, M>(self, mut f: H) -> Result<(), Error>
+    where
+        H: CronHandler<S, M>,
+    {
+        self.run(move |req: Request, state: State<S>| handle(&mut f, verify(&req), &state))
+            .await
+    }
+}
+
+fn handle<S, M, H: CronHandler<S, M>>(
+    f: &mut H,
+    verified: Result<CronInvocation, CronError>,
+    state: &State<S>,
+) -> CronFuture<H::Future> {
+    match verified {
+        Ok(cron) => CronFuture::Handler {
+            inner: f.call(cron, state),
+        },
+        Err(e) => CronFuture::Rejected {
+            response: Some(e.into_response()),
+        },
+    }
+}
+
+pin_project! {
+    #[project = CronFutureProj]
+    pub enum CronFuture<F> {
+        Handler {
+            #[pin]
+            inner: F,
+        },
+        Rejected {
+            response: Option<Response<Body>>,
+        },
+    }
+}
+
+impl<F> Future for CronFuture<F>
+where
+    F: Future<Output = Result<Response<Body>, Error>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            CronFutureProj::Handler { inner } => inner.poll(cx),
+            CronFutureProj::Rejected { response } => Poll::Ready(Ok(response
+                .take()
+                .expect("CronFuture polled after completion"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{constant_time_eq, handle, verify_secret, CronError};
+    use crate::{Body, Error, Request, Response, State};
+    use lambda_http::http::StatusCode;
+    use std::time::UNIX_EPOCH;
+
+    fn request(authorization: Option<&str>) -> Request {
+        let mut builder = lambda_http::http::Request::builder()
+            .uri("https://example.vercel.app/api/cron?job=weekly%20digest&dry_run");
+        if let Some(authorization) = authorization {
+            builder = builder.header("authorization", authorization);
+        }
+        builder.body(Body::Empty).unwrap()
+    }
+
+    #[test]
+    fn it_compares_in_constant_time() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+    }
+
+    #[tokio::test]
+    async fn it_verifies_the_cron_secret() {
+        let state = State::new(());
+        let mut handler = |cron: super::CronInvocation| async move {
+            assert_eq!(cron.path, "/api/cron");
+            assert_eq!(cron.query["job"], "weekly digest");
+            assert_eq!(cron.query["dry_run"], "");
+            let invoked = cron.invoked_at.duration_since(UNIX_EPOCH).unwrap();
+            assert_eq!(invoked.as_secs() % 60, 0);
+            Ok::<_, Error>(Response::new(Body::Text("ran".to_string())))
+        };
+
+        let verified = verify_secret(&request(Some("Bearer s3cr3t")), "s3cr3t");
+        let response = handle(&mut handler, verified, &state).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), &Body::Text("ran".to_string()));
+
+        for authorization in [None, Some("Bearer wrong"), Some("s3cr3t"), Some("Bearer ")] {
+            let verified = verify_secret(&request(authorization), "s3cr3t");
+            let response = handle(&mut handler, verified, &state).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        assert_eq!(
+            verify_secret(&request(Some("Bearer ")), "").unwrap_err(),
+            CronError::SecretMissing
+        );
+    }
+}
@@ -1,6 +1,25 @@
+#[cfg(feature = "auth")]
+pub mod auth;
+pub mod cors;
+pub mod cron;
 pub mod http;
+pub mod limits;
+pub mod logging;
+pub mod metrics;
+pub mod middleware;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod rate_limit;
 pub mod request;
 pub mod response;
+mod runtime;
+mod state;
+#[cfg(test)]
+mod testing;
+#[cfg(any(feature = "auth", feature = "redis"))]
+mod tls;
+#[cfg(feature = "webhooks")]
+pub mod webhooks;
 
 use request::Event;
 use request::VercelRequest;
@@ -9,35 +28,36 @@ use std::future::Future;
 use tracing::{debug, error};
 
 pub use vercel_runtime_macro::bundled_api;
-pub use vercel_runtime_router::{Route, Router};
+pub use vercel_runtime_router::{
+    register_constraint, ConstraintError, Params, Route, Router, UrlError,
+};
 
 pub use lambda_http::{
     http::StatusCode, service_fn, tower::ServiceBuilder, Body, Error, Request, RequestPayloadExt,
     Response,
 };
 pub use lambda_runtime::run as run_service;
+pub use runtime::{Invocation, Runtime};
+pub use state::{Handler, State};
 
 pub async fn run<T: FnMut(Request) -> F, F: Future<Output = Result<Response<Body>, Error>>>(
     f: T,
 ) -> Result<(), Error> {
-    let handler = ServiceBuilder::new()
-        .map_request(process_request)
-        .map_response(process_response)
-        .service(service_fn(f));
-
-    lambda_runtime::run(handler).await
+    Runtime::new().run(f).await
 }
 
 pub fn process_request(event: Event) -> Request {
-    let (event, _context) = event.into_parts();
+    let (event, context) = event.into_parts();
     let parse_result = serde_json::from_str::<VercelRequest>(&event.body);
 
     match parse_result {
         Ok(request) => {
             debug!("Deserialized Vercel proxy request successfully");
             debug!("Request: {:?}", request);
-            let http_req: lambda_http::http::Request<Body> = request.into();
-            http_req.map(|b| b)
+            let mut http_req: lambda_http::http::Request<Body> = request.into();
+            // Handlers and `limits::TimeoutLayer` read the invocation's deadline from it
+            http_req.extensions_mut().insert(context);
+            http_req
         }
         Err(e) => {
             error!("Could not deserialize event body to VercelRequest {:?}", e);
@@ -0,0 +1,366 @@
+//! Request body size limits and handler timeouts.
+//!
+//! [`crate::run`] and [`crate::Runtime`] reject bodies larger than [`DEFAULT_BODY_LIMIT`] with
+//! `413 Payload Too Large`, and answer `504 Gateway Timeout` when the handler is still running
+//! shortly before the Lambda deadline, instead of letting Vercel kill the function without a
+//! response. Both are tower layers, so they can be added to other stacks too, e.g.
+//! `vercel_axum`'s.
+
+use http_body::Body as HttpBody;
+use lambda_http::http::{header, HeaderValue, Request, Response, StatusCode};
+use lambda_http::tower::Layer;
+use lambda_runtime::Context as LambdaContext;
+use pin_project_lite::pin_project;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+use tokio::time::Sleep;
+use tower_service::Service;
+use tracing::{error, warn};
+
+/// Maximum request body size in bytes.
+pub const BODY_LIMIT_ENV: &str = "VERCEL_BODY_LIMIT";
+/// Time in milliseconds reserved before the Lambda deadline to send the timeout response.
+pub const TIMEOUT_MARGIN_ENV: &str = "VERCEL_TIMEOUT_MARGIN_MS";
+
+/// Vercel's request body limit of 4.5 MB.
+pub const DEFAULT_BODY_LIMIT: usize = 4_718_592;
+pub const DEFAULT_TIMEOUT_MARGIN: Duration = Duration::from_millis(500);
+
+fn env_var<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let value = std::env::var(name).ok()?;
+    match value.trim().parse() {
+        Ok(value) => Some(value),
+        Err(_) => {
+            error!("Ignoring invalid {}: {:?}", name, value);
+            None
+        }
+    }
+}
+
+fn error_response<B: From<String>>(status: StatusCode, code: &str, message: &str) -> Response<B> {
+    let body = serde_json::json!({ "message": message, "code": code });
+    let mut response = Response::new(B::from(body.to_string()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+/// Layer answering `413` to requests whose body is larger than the limit, according to their
+/// `Content-Length` header or their body's size.
+#[derive(Debug, Clone, Copy)]
+pub struct BodyLimitLayer {
+    limit: usize,
+}
+
+impl Default for BodyLimitLayer {
+    fn default() -> Self {
+        Self::new(DEFAULT_BODY_LIMIT)
+    }
+}
+
+impl BodyLimitLayer {
+    pub fn new(limit: usize) -> Self {
+        BodyLimitLayer { limit }
+    }
+
+    /// Reads the limit from `VERCEL_BODY_LIMIT`, defaulting to [`DEFAULT_BODY_LIMIT`].
+    pub fn from_env() -> Self {
+        Self::new(env_var(BODY_LIMIT_ENV).unwrap_or(DEFAULT_BODY_LIMIT))
+    }
+
+    fn exceeded<B: HttpBody>(&self, req: &Request<B>) -> bool {
+        let content_length = req
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+
+        content_length.unwrap_or_default() > self.limit as u64
+            || req.body().size_hint().lower() > self.limit as u64
+    }
+}
+
+impl<S> Layer<S> for BodyLimitLayer {
+    type Service = BodyLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BodyLimitService {
+            inner,
+            layer: *self,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct BodyLimitService<S> {
+    inner: S,
+    layer: BodyLimitLayer,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for BodyLimitService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    B: HttpBody,
+    ResBody: From<String>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = LimitFuture<S::Future, ResBody>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        if self.layer.exceeded(&req) {
+            warn!(
+                "Rejected request body larger than {} bytes",
+                self.layer.limit
+            );
+            let message = format!("request body is larger than {} bytes", self.layer.limit);
+            return LimitFuture::Rejected {
+                response: Some(error_response(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "payload_too_large",
+                    &message,
+                )),
+            };
+        }
+
+        LimitFuture::Inner {
+            inner: self.inner.call(req),
+            sleep: None,
+        }
+    }
+}
+
+/// Layer answering `504` if the handler has not responded by the Lambda deadline minus a
+/// margin, dropping its future.
+///
+/// The deadline is read from the `lambda_runtime::Context` in the request's extensions, which
+/// [`crate::process_request`] adds. Requests without one are only limited by
+/// [`TimeoutLayer::max`].
+#[derive(Debug, Clone, Copy)]
+pub struct TimeoutLayer {
+    margin: Duration,
+    max: Option<Duration>,
+}
+
+impl Default for TimeoutLayer {
+    fn default() -> Self {
+        Self::new(DEFAULT_TIMEOUT_MARGIN)
+    }
+}
+
+impl TimeoutLayer {
+    pub fn new(margin: Duration) -> Self {
+        TimeoutLayer { margin, max: None }
+    }
+
+    /// Reads the margin from `VERCEL_TIMEOUT_MARGIN_MS`, defaulting to
+    /// [`DEFAULT_TIMEOUT_MARGIN`].
+    pub fn from_env() -> Self {
+        let margin = env_var(TIMEOUT_MARGIN_ENV)
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_TIMEOUT_MARGIN);
+        Self::new(margin)
+    }
+
+    /// Also times out handlers running longer than `max`, regardless of the deadline.
+    pub fn max(mut self, max: Duration) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    fn timeout<B>(&self, req: &Request<B>) -> Option<Duration> {
+        let remaining = req.extensions().get::<LambdaContext>().map(|context| {
+            context
+                .deadline()
+                .duration_since(SystemTime::now())
+                .unwrap_or_default()
+                .saturating_sub(self.margin)
+        });
+
+        match (remaining, self.max) {
+            (Some(remaining), Some(max)) => Some(remaining.min(max)),
+            (remaining, max) => remaining.or(max),
+        }
+    }
+}
+
+impl<S> Layer<S> for TimeoutLayer {
+    type Service = TimeoutService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TimeoutService {
+            inner,
+            layer: *self,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TimeoutService<S> {
+    inner: S,
+    layer: TimeoutLayer,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for TimeoutService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    ResBody: From<String>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = LimitFuture<S::Future, ResBody>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let sleep = self.layer.timeout(&req).map(tokio::time::sleep);
+        LimitFuture::Inner {
+            inner: self.inner.call(req),
+            sleep,
+        }
+    }
+}
+
+pin_project! {
+    #[project = LimitFutureProj]
+    pub enum LimitFuture<F, B> {
+        Inner {
+            #[pin]
+            inner: F,
+            #[pin]
+            sleep: Option<Sleep>,
+        },
+        Rejected {
+            response: Option<Response<B>>,
+        },
+    }
+}
+
+impl<F, B, E> Future for LimitFuture<F, B>
+where
+    F: Future<Output = Result<Response<B>, E>>,
+    B: From<String>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            LimitFutureProj::Inner { inner, sleep } => {
+                if let Poll::Ready(result) = inner.poll(cx) {
+                    return Poll::Ready(result);
+                }
+                match sleep.as_pin_mut().map(|sleep| sleep.poll(cx)) {
+                    Some(Poll::Ready(())) => {
+                        error!("Handler timed out before the function's deadline");
+                        Poll::Ready(Ok(error_response(
+                            StatusCode::GATEWAY_TIMEOUT,
+                            "timeout",
+                            "handler timed out",
+                        )))
+                    }
+                    _ => Poll::Pending,
+                }
+            }
+            LimitFutureProj::Rejected { response } => Poll::Ready(Ok(response
+                .take()
+                .expect("LimitFuture polled after completion"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BodyLimitLayer, TimeoutLayer};
+    use lambda_http::http::{Request, Response, StatusCode};
+    use lambda_http::tower::Layer;
+    use lambda_http::{service_fn, Body, Error};
+    use lambda_runtime::Context as LambdaContext;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use tower_service::Service;
+
+    async fn echo(req: Request<Body>) -> Result<Response<Body>, Error> {
+        Ok(Response::new(req.into_body()))
+    }
+
+    #[tokio::test]
+    async fn it_rejects_large_bodies() {
+        let mut service = BodyLimitLayer::new(4).layer(service_fn(echo));
+
+        let response = service
+            .call(Request::new(Body::Text("1234".to_string())))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = service
+            .call(Request::new(Body::Binary(vec![0; 5])))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(response.headers()["content-type"], "application/json");
+
+        let declared = Request::builder()
+            .header("content-length", "1000")
+            .body(Body::Empty)
+            .unwrap();
+        let response = service.call(declared).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn it_times_out_before_the_deadline() {
+        let mut service = TimeoutLayer::new(Duration::from_millis(100)).layer(service_fn(
+            |req: Request<Body>| async move {
+                if req.uri().path() == "/slow" {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                }
+                Ok::<_, Error>(Response::new(Body::Empty))
+            },
+        ));
+
+        let request = |path: &str| {
+            let deadline = SystemTime::now() + Duration::from_millis(150);
+            let mut context = LambdaContext::default();
+            context.deadline = deadline.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+            Request::builder()
+                .uri(path)
+                .extension(context)
+                .body(Body::Empty)
+                .unwrap()
+        };
+
+        let response = service.call(request("/fast")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let start = std::time::Instant::now();
+        let response = service.call(request("/slow")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        let Body::Text(body) = response.body() else {
+            panic!("expected a JSON body");
+        };
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["code"], "timeout");
+
+        // Without a Lambda context only `max` applies
+        let mut service = TimeoutLayer::default()
+            .max(Duration::from_millis(10))
+            .layer(service_fn(|_req: Request<Body>| async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok::<_, Error>(Response::new(Body::Empty))
+            }));
+        let response = service.call(Request::new(Body::Empty)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+}
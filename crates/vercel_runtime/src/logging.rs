@@ -0,0 +1,218 @@
+//! Structured logging for Vercel functions.
+//!
+//! [`init`] installs a subscriber printing JSON records that Vercel log drains can index, and
+//! [`LogLayer`] wraps every invocation in a `request` span, which [`crate::run`] and
+//! `vercel_axum::run` add to their stacks. `vercel_axum::run` installs the subscriber too, so it
+//! now logs at `info` by default instead of only printing errors; set `RUST_LOG=error` to keep
+//! the previous output.
+
+use lambda_http::http::{Request, Response};
+use lambda_http::tower::Layer;
+use pin_project_lite::pin_project;
+use std::fmt::Display;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Instant;
+use tower_service::Service;
+use tracing::field::Empty;
+use tracing::instrument::Instrumented;
+use tracing::{error, info, info_span, Instrument, Span};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::EnvFilter;
+
+use crate::Error;
+
+/// Header Vercel sets to identify a request across its infrastructure.
+pub const VERCEL_ID_HEADER: &str = "x-vercel-id";
+
+/// Installs a global subscriber printing one JSON record per line to stdout, filtered via
+/// `RUST_LOG` (defaults to `info`).
+///
+/// Panics if a global subscriber has already been installed, use [`try_init`] otherwise.
+pub fn init() {
+    try_init().expect("failed to install the logging subscriber")
+}
+
+/// Like [`init`], but returns an error if a global subscriber has already been installed.
+pub fn try_init() -> Result<(), Error> {
+    tracing::subscriber::set_global_default(subscriber(std::io::stdout))?;
+    Ok(())
+}
+
+/// Builds the JSON subscriber used by [`init`], writing to `make_writer`.
+pub fn subscriber<W>(make_writer: W) -> impl tracing::Subscriber + Send + Sync
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(filter)
+        .with_writer(make_writer)
+        .with_target(false)
+        .with_current_span(true)
+        .with_span_list(false)
+        .flatten_event(true)
+        .finish()
+}
+
+/// Layer wrapping each invocation in a `request` span carrying the Vercel request id, method
+/// and path, and recording status and latency once the handler completes.
+#[derive(Clone, Copy, Default)]
+pub struct LogLayer;
+
+impl<S> Layer<S> for LogLayer {
+    type Service = LogService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        LogService { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct LogService<S> {
+    inner: S,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for LogService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Error: Display,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = LogFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let request_id = req
+            .headers()
+            .get(VERCEL_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+
+        let span = info_span!(
+            "request",
+            request_id = %request_id,
+            method = %req.method(),
+            path = %req.uri().path(),
+            status = Empty,
+            latency_ms = Empty,
+        );
+
+        LogFuture {
+            inner: self.inner.call(req).instrument(span.clone()),
+            span,
+            start: Instant::now(),
+        }
+    }
+}
+
+pin_project! {
+    pub struct LogFuture<F> {
+        #[pin]
+        inner: Instrumented<F>,
+        span: Span,
+        start: Instant,
+    }
+}
+
+impl<F, ResBody, E> Future for LogFuture<F>
+where
+    F: Future<Output = Result<Response<ResBody>, E>>,
+    E: Display,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = ready!(this.inner.poll(cx));
+
+        let _enter = this.span.enter();
+        this.span
+            .record("latency_ms", this.start.elapsed().as_millis() as u64);
+
+        match &result {
+            Ok(response) => {
+                this.span.record("status", response.status().as_u16());
+                info!("request completed");
+            }
+            Err(e) => {
+                this.span.record("status", 500);
+                error!("request failed: {}", e);
+            }
+        }
+
+        Poll::Ready(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{subscriber, LogLayer};
+    use lambda_http::http::{Request, Response, StatusCode};
+    use lambda_http::tower::Layer;
+    use lambda_http::{service_fn, Body, Error};
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use tower_service::Service;
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn it_logs_request_fields_as_json() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let _guard = tracing::subscriber::set_default(subscriber(move || writer.clone()));
+
+        let mut service = LogLayer.layer(service_fn(|_req: Request<Body>| async {
+            tracing::info!("handling request");
+            Ok::<_, Error>(
+                Response::builder()
+                    .status(StatusCode::CREATED)
+                    .body(Body::Empty)
+                    .unwrap(),
+            )
+        }));
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("https://example.vercel.app/api/users")
+            .header("x-vercel-id", "fra1::abc123")
+            .body(Body::Empty)
+            .unwrap();
+        service.call(request).await.unwrap();
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let records = output
+            .lines()
+            .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["message"], "handling request");
+        assert_eq!(records[0]["span"]["request_id"], "fra1::abc123");
+        assert_eq!(records[0]["span"]["method"], "POST");
+        assert_eq!(records[0]["span"]["path"], "/api/users");
+
+        assert_eq!(records[1]["message"], "request completed");
+        assert_eq!(records[1]["span"]["status"], 201);
+        assert!(records[1]["span"]["latency_ms"].is_number());
+    }
+}
@@ -0,0 +1,325 @@
+//! Per-invocation metrics recorded through the [`metrics`](https://docs.rs/metrics) facade.
+//!
+//! Nothing is recorded unless a recorder (e.g. a Prometheus or StatsD exporter) is installed.
+
+use ::metrics::{counter, histogram, Label};
+use http_body::Body as HttpBody;
+use lambda_http::http::{HeaderValue, Request, Response};
+use lambda_http::tower::Layer;
+use pin_project_lite::pin_project;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+use tower_service::Service;
+
+/// Counter of invocations, labelled with `route`, `status` and `cold_start`.
+pub const INVOCATIONS: &str = "vercel_function_invocations_total";
+/// Histogram of handler latency in seconds, labelled with `route` and `status`.
+pub const DURATION: &str = "vercel_function_duration_seconds";
+/// Histogram of response body sizes in bytes, labelled with `route` and `status`.
+pub const RESPONSE_SIZE: &str = "vercel_function_response_size_bytes";
+/// Counter of cold starts.
+pub const COLD_STARTS: &str = "vercel_function_cold_starts_total";
+/// Histogram of the time between the runtime being set up and the first invocation in seconds.
+pub const INIT_DURATION: &str = "vercel_function_init_duration_seconds";
+
+static INIT_START: OnceLock<Instant> = OnceLock::new();
+
+/// Response extension naming the route file which handled the request, e.g.
+/// `api/users/[id].rs`. Handlers generated by `bundled_api` add it, and [`MetricsLayer`] uses it
+/// as the `route` label.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchedRoute(pub String);
+
+type RouteLabel = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+/// Layer recording cold starts, init duration, handler latency, status code and response size
+/// for every invocation, optionally reporting them as a `Server-Timing` response header.
+#[derive(Clone)]
+pub struct MetricsLayer {
+    server_timing: bool,
+    route_label: Option<RouteLabel>,
+    cold_start: Arc<AtomicBool>,
+}
+
+impl Default for MetricsLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetricsLayer {
+    /// Creates the layer. The first layer created, e.g. by `Runtime::new`, marks the start of
+    /// the function's initialisation. The first invocation through the layer (or its clones)
+    /// counts as the cold start.
+    pub fn new() -> Self {
+        INIT_START.get_or_init(Instant::now);
+
+        MetricsLayer {
+            server_timing: false,
+            route_label: None,
+            cold_start: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    /// Adds a `Server-Timing` header with the handler (and on cold starts, init) duration.
+    pub fn server_timing(mut self, enabled: bool) -> Self {
+        self.server_timing = enabled;
+        self
+    }
+
+    /// Maps request paths to the `route` label, e.g. `/api/users/42` to `api/users/[id]`.
+    /// The mapping should keep the label's cardinality bounded.
+    ///
+    /// By default, the label is the [`MatchedRoute`] of the response, and left out without one.
+    pub fn route_label<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.route_label = Some(Arc::new(f));
+        self
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = MetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MetricsService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MetricsService<S> {
+    inner: S,
+    layer: MetricsLayer,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for MetricsService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    ResBody: HttpBody,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = MetricsFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let start = Instant::now();
+        let cold_start = self.layer.cold_start.swap(false, Ordering::Relaxed);
+
+        let init_duration = match cold_start {
+            true => {
+                let init = start.duration_since(*INIT_START.get_or_init(Instant::now));
+                counter!(COLD_STARTS).increment(1);
+                histogram!(INIT_DURATION).record(init.as_secs_f64());
+                Some(init)
+            }
+            false => None,
+        };
+
+        let route = self.layer.route_label.as_ref().map(|f| f(req.uri().path()));
+
+        MetricsFuture {
+            inner: self.inner.call(req),
+            start,
+            cold_start,
+            init_duration,
+            route,
+            server_timing: self.layer.server_timing,
+        }
+    }
+}
+
+pin_project! {
+    pub struct MetricsFuture<F> {
+        #[pin]
+        inner: F,
+        start: Instant,
+        cold_start: bool,
+        init_duration: Option<Duration>,
+        route: Option<String>,
+        server_timing: bool,
+    }
+}
+
+impl<F, ResBody, E> Future for MetricsFuture<F>
+where
+    F: Future<Output = Result<Response<ResBody>, E>>,
+    ResBody: HttpBody,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut result = ready!(this.inner.poll(cx));
+        let duration = this.start.elapsed();
+
+        let route = this.route.take().or_else(|| {
+            let response = result.as_ref().ok()?;
+            Some(response.extensions().get::<MatchedRoute>()?.0.clone())
+        });
+        let status = match &result {
+            Ok(response) => response.status().as_u16().to_string(),
+            Err(_) => "error".to_string(),
+        };
+
+        let mut labels = vec![];
+        if let Some(route) = route {
+            labels.push(Label::new("route", route));
+        }
+        labels.push(Label::new("status", status));
+
+        let mut invocation_labels = labels.clone();
+        invocation_labels.push(Label::new("cold_start", this.cold_start.to_string()));
+        counter!(INVOCATIONS, invocation_labels).increment(1);
+        histogram!(DURATION, labels.clone()).record(duration.as_secs_f64());
+
+        if let Ok(response) = &mut result {
+            let body = response.body();
+            let size = match body.is_end_stream() {
+                true => 0,
+                false => {
+                    let hint = body.size_hint();
+                    hint.exact().unwrap_or(hint.lower())
+                }
+            };
+            histogram!(RESPONSE_SIZE, labels).record(size as f64);
+
+            if *this.server_timing {
+                let mut timing = format!("handler;dur={:.3}", duration.as_secs_f64() * 1000.0);
+                if let Some(init) = this.init_duration {
+                    timing.push_str(&format!(", init;dur={:.3}", init.as_secs_f64() * 1000.0));
+                }
+                if let Ok(value) = HeaderValue::from_str(&timing) {
+                    response.headers_mut().append("server-timing", value);
+                }
+            }
+        }
+
+        Poll::Ready(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MatchedRoute, MetricsLayer, COLD_STARTS, DURATION, INVOCATIONS, RESPONSE_SIZE};
+    use lambda_http::http::{Request, Response};
+    use lambda_http::tower::Layer;
+    use lambda_http::{service_fn, Body, Error};
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use tower_service::Service;
+
+    #[tokio::test]
+    async fn it_records_invocation_metrics() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let _guard = ::metrics::set_default_local_recorder(&recorder);
+
+        let mut service = MetricsLayer::new()
+            .server_timing(true)
+            .route_label(|path| path.trim_end_matches(char::is_numeric).to_string())
+            .layer(service_fn(|_req: Request<Body>| async {
+                Ok::<_, Error>(Response::new(Body::Text("hello".to_string())))
+            }));
+
+        for id in ["1", "2"] {
+            let request = Request::builder()
+                .uri(format!("https://example.vercel.app/api/users/{}", id))
+                .body(Body::Empty)
+                .unwrap();
+            let response = service.call(request).await.unwrap();
+
+            let timing = response.headers()["server-timing"].to_str().unwrap();
+            assert!(timing.starts_with("handler;dur="));
+            assert_eq!(timing.contains("init;dur="), id == "1");
+        }
+
+        // Already warm, so only the first layer above records a cold start
+        let warm = MetricsLayer {
+            cold_start: Arc::new(AtomicBool::new(false)),
+            ..MetricsLayer::new()
+        };
+        let mut service = warm.layer(service_fn(|req: Request<Body>| async move {
+            let mut response = Response::new(Body::Empty);
+            if req.uri().path() != "/api/unknown" {
+                let route = MatchedRoute("api/posts/[id].rs".to_string());
+                response.extensions_mut().insert(route);
+            }
+            Ok::<_, Error>(response)
+        }));
+
+        for path in ["/api/posts/1", "/api/unknown"] {
+            let request = Request::builder()
+                .uri(format!("https://example.vercel.app{}", path))
+                .body(Body::Empty)
+                .unwrap();
+            let response = service.call(request).await.unwrap();
+            assert!(!response.headers().contains_key("server-timing"));
+        }
+
+        let metrics = snapshotter.snapshot().into_vec();
+        let value = |name: &str, labels: &[(&str, &str)]| {
+            metrics
+                .iter()
+                .find(|(key, _, _, _)| {
+                    let key = key.key();
+                    key.name() == name
+                        && labels
+                            .iter()
+                            .all(|(k, v)| key.labels().any(|l| l.key() == *k && l.value() == *v))
+                })
+                .map(|(_, _, _, value)| value)
+        };
+
+        let route = ("route", "/api/users/");
+        assert_eq!(
+            value(
+                INVOCATIONS,
+                &[route, ("status", "200"), ("cold_start", "true")]
+            ),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(
+            value(
+                INVOCATIONS,
+                &[route, ("status", "200"), ("cold_start", "false")]
+            ),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(value(COLD_STARTS, &[]), Some(&DebugValue::Counter(1)));
+        assert!(matches!(
+            value(DURATION, &[route]),
+            Some(DebugValue::Histogram(v)) if v.len() == 2
+        ));
+        assert!(matches!(
+            value(RESPONSE_SIZE, &[route]),
+            Some(DebugValue::Histogram(v)) if v.iter().all(|s| s.into_inner() == 5.0)
+        ));
+
+        assert_eq!(
+            value(INVOCATIONS, &[("route", "api/posts/[id].rs")]),
+            Some(&DebugValue::Counter(1))
+        );
+        let unlabelled = metrics
+            .iter()
+            .filter(|(key, _, _, _)| {
+                key.key().name() == INVOCATIONS && key.key().labels().all(|l| l.key() != "route")
+            })
+            .map(|(_, _, _, value)| value)
+            .collect::<Vec<_>>();
+        assert_eq!(unlabelled, [&DebugValue::Counter(1)]);
+    }
+}
@@ -0,0 +1,97 @@
+//! Middleware for routes dispatched by [`crate::bundled_api`].
+//!
+//! A middleware is an async function taking the request and the [`Next`] handler in the chain:
+//!
+//! ```no_run
+//! use vercel_runtime::middleware::Next;
+//! use vercel_runtime::{Body, Error, Request, Response, StatusCode};
+//!
+//! pub async fn middleware(req: Request, next: Next) -> Result<Response<Body>, Error> {
+//!     if !req.headers().contains_key("authorization") {
+//!         return Ok(Response::builder()
+//!             .status(StatusCode::UNAUTHORIZED)
+//!             .body(Body::Empty)?);
+//!     }
+//!
+//!     next.run(req).await
+//! }
+//! ```
+//!
+//! `bundled_api` applies a `middleware.rs` file to all routes in its directory and below, with
+//! middleware of parent directories running first.
+
+use lambda_http::{Body, Error, Request, Response};
+use std::future::Future;
+use std::pin::Pin;
+
+type BoxFuture = Pin<Box<dyn Future<Output = Result<Response<Body>, Error>>>>;
+
+/// The remainder of the middleware chain, ending with the route's handler.
+pub struct Next {
+    f: Box<dyn FnOnce(Request) -> BoxFuture>,
+}
+
+impl Next {
+    pub fn new<F, Fut>(f: F) -> Self
+    where
+        F: FnOnce(Request) -> Fut + 'static,
+        Fut: Future<Output = Result<Response<Body>, Error>> + 'static,
+    {
+        Next {
+            f: Box::new(move |req| Box::pin(f(req))),
+        }
+    }
+
+    /// Passes the request on to the next middleware or the route's handler.
+    pub async fn run(self, req: Request) -> Result<Response<Body>, Error> {
+        (self.f)(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Next;
+    use lambda_http::http::HeaderValue;
+    use lambda_http::{Body, Error, Request, Response};
+
+    async fn outer(mut req: Request, next: Next) -> Result<Response<Body>, Error> {
+        req.headers_mut()
+            .insert("x-chain", HeaderValue::from_static("outer"));
+        let mut response = next.run(req).await?;
+        response
+            .headers_mut()
+            .append("x-chain", HeaderValue::from_static("outer"));
+        Ok(response)
+    }
+
+    async fn inner(req: Request, next: Next) -> Result<Response<Body>, Error> {
+        let mut response = next.run(req).await?;
+        response
+            .headers_mut()
+            .append("x-chain", HeaderValue::from_static("inner"));
+        Ok(response)
+    }
+
+    async fn handler(req: Request) -> Result<Response<Body>, Error> {
+        let mut response = Response::new(Body::Empty);
+        response
+            .headers_mut()
+            .append("x-chain", req.headers()["x-chain"].clone());
+        Ok(response)
+    }
+
+    #[tokio::test]
+    async fn it_runs_middleware_outermost_first() {
+        let next = Next::new(handler);
+        let next = Next::new(move |req| inner(req, next));
+        let response = outer(Request::new(Body::Empty), next).await.unwrap();
+
+        let chain = response
+            .headers()
+            .get_all("x-chain")
+            .iter()
+            .map(|v| v.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(chain, ["outer", "inner", "outer"]);
+    }
+}
@@ -0,0 +1,389 @@
+//! OpenTelemetry tracing export, enabled by the `otel` feature.
+//!
+//! Spans are buffered in memory and exported over OTLP before each invocation returns, since
+//! the execution environment may be frozen as soon as the response has been sent.
+
+use lambda_http::http::{HeaderMap, Request, Response};
+use lambda_http::tower::Layer;
+use opentelemetry::context::{FutureExt, WithContext};
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::trace::{SpanKind, Status, TraceContextExt, Tracer, TracerProvider};
+use opentelemetry::{global, Context as OtelContext, KeyValue};
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{
+    SdkTracer, SdkTracerProvider, Span, SpanData, SpanExporter, SpanProcessor,
+};
+use opentelemetry_sdk::Resource;
+use pin_project_lite::pin_project;
+use std::fmt::Display;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tower_service::Service;
+use tracing::error;
+
+use crate::Error;
+
+static INSTALLED: OnceLock<OtelLayer> = OnceLock::new();
+
+/// Installs an OTLP/HTTP exporter configured via the standard `OTEL_EXPORTER_OTLP_*` and
+/// `OTEL_SERVICE_NAME` environment variables, and the W3C trace context propagator.
+///
+/// Once installed, [`crate::run`] creates a server span for every invocation.
+pub fn init() -> Result<(), Error> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()?;
+    let layer = OtelLayer::new(exporter);
+
+    global::set_tracer_provider(layer.tracer_provider().clone());
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    INSTALLED
+        .set(layer)
+        .map_err(|_| "OpenTelemetry has already been initialized")?;
+    Ok(())
+}
+
+/// Returns the layer installed by [`init`], if any.
+pub fn layer() -> Option<OtelLayer> {
+    INSTALLED.get().cloned()
+}
+
+/// Span processor holding finished spans until the invocation flushes them.
+#[derive(Debug, Default)]
+struct PendingSpans(Arc<Mutex<Vec<SpanData>>>);
+
+impl SpanProcessor for PendingSpans {
+    fn on_start(&self, _span: &mut Span, _cx: &OtelContext) {}
+
+    fn on_end(&self, span: SpanData) {
+        self.0.lock().unwrap().push(span);
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+struct Shared<E> {
+    provider: SdkTracerProvider,
+    tracer: SdkTracer,
+    pending: Arc<Mutex<Vec<SpanData>>>,
+    exporter: tokio::sync::Mutex<E>,
+}
+
+impl<E: SpanExporter> Shared<E> {
+    async fn flush(self: Arc<Self>) {
+        let batch = std::mem::take(&mut *self.pending.lock().unwrap());
+        if batch.is_empty() {
+            return;
+        }
+
+        let exporter = self.exporter.lock().await;
+        if let Err(e) = exporter.export(batch).await {
+            error!("Could not export spans: {}", e);
+        }
+    }
+}
+
+/// Layer creating an OpenTelemetry server span per request, continuing the trace from the
+/// incoming `traceparent`/`tracestate` headers, and exporting all finished spans before the
+/// response is returned.
+pub struct OtelLayer<E = opentelemetry_otlp::SpanExporter> {
+    shared: Arc<Shared<E>>,
+}
+
+impl<E> Clone for OtelLayer<E> {
+    fn clone(&self) -> Self {
+        OtelLayer {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<E: SpanExporter + 'static> OtelLayer<E> {
+    pub fn new(exporter: E) -> Self {
+        let pending = PendingSpans::default();
+        let buffer = pending.0.clone();
+
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(pending)
+            .with_resource(Resource::builder().build())
+            .build();
+        let tracer = provider.tracer("vercel_runtime");
+
+        OtelLayer {
+            shared: Arc::new(Shared {
+                provider,
+                tracer,
+                pending: buffer,
+                exporter: tokio::sync::Mutex::new(exporter),
+            }),
+        }
+    }
+
+    /// The provider whose spans are exported by this layer, e.g. to create spans in handlers.
+    pub fn tracer_provider(&self) -> &SdkTracerProvider {
+        &self.shared.provider
+    }
+}
+
+impl<S, E> Layer<S> for OtelLayer<E> {
+    type Service = OtelService<S, E>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        OtelService {
+            inner,
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+pub struct OtelService<S, E = opentelemetry_otlp::SpanExporter> {
+    inner: S,
+    shared: Arc<Shared<E>>,
+}
+
+impl<S: Clone, E> Clone for OtelService<S, E> {
+    fn clone(&self) -> Self {
+        OtelService {
+            inner: self.inner.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+impl<S, E, B, ResBody> Service<Request<B>> for OtelService<S, E>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Error: Display,
+    E: SpanExporter + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = OtelFuture<S::Future, E>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let parent = TraceContextPropagator::new().extract(&HeaderExtractor(req.headers()));
+
+        let mut attributes = vec![
+            KeyValue::new("http.request.method", req.method().to_string()),
+            KeyValue::new("url.path", req.uri().path().to_string()),
+            KeyValue::new(
+                "url.scheme",
+                req.uri().scheme_str().unwrap_or("https").to_string(),
+            ),
+        ];
+        if let Some(query) = req.uri().query() {
+            attributes.push(KeyValue::new("url.query", query.to_string()));
+        }
+        if let Some(host) = req.uri().host() {
+            attributes.push(KeyValue::new("server.address", host.to_string()));
+        }
+        if let Some(user_agent) = req
+            .headers()
+            .get("user-agent")
+            .and_then(|v| v.to_str().ok())
+        {
+            attributes.push(KeyValue::new("user_agent.original", user_agent.to_string()));
+        }
+
+        let span = self
+            .shared
+            .tracer
+            .span_builder(req.method().to_string())
+            .with_kind(SpanKind::Server)
+            .with_attributes(attributes)
+            .start_with_context(&self.shared.tracer, &parent);
+        let otel_cx = parent.with_span(span);
+
+        OtelFuture {
+            inner: self.inner.call(req).with_context(otel_cx.clone()),
+            otel_cx,
+            shared: self.shared.clone(),
+            result: None,
+            flush: None,
+        }
+    }
+}
+
+pin_project! {
+    pub struct OtelFuture<F: Future, E> {
+        #[pin]
+        inner: WithContext<F>,
+        otel_cx: OtelContext,
+        shared: Arc<Shared<E>>,
+        result: Option<F::Output>,
+        flush: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    }
+}
+
+impl<F, E, ResBody, Err> Future for OtelFuture<F, E>
+where
+    F: Future<Output = Result<Response<ResBody>, Err>>,
+    Err: Display,
+    E: SpanExporter + 'static,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+
+        if this.flush.is_none() {
+            let result = ready!(this.inner.poll(cx));
+
+            let span = this.otel_cx.span();
+            match &result {
+                Ok(response) => {
+                    let status = response.status();
+                    span.set_attribute(KeyValue::new(
+                        "http.response.status_code",
+                        status.as_u16() as i64,
+                    ));
+                    if status.is_server_error() {
+                        span.set_status(Status::error(status.to_string()));
+                    }
+                }
+                Err(e) => span.set_status(Status::error(e.to_string())),
+            }
+            span.end();
+
+            *this.result = Some(result);
+            *this.flush = Some(Box::pin(this.shared.clone().flush()));
+        }
+
+        if let Some(flush) = this.flush.as_mut() {
+            ready!(flush.as_mut().poll(cx));
+        }
+
+        Poll::Ready(
+            this.result
+                .take()
+                .expect("OtelFuture polled after completion"),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OtelLayer;
+    use crate::testing::{ok, stand_in};
+    use lambda_http::http::{Request, Response, StatusCode};
+    use lambda_http::tower::Layer;
+    use lambda_http::{service_fn, Body, Error};
+    use opentelemetry_otlp::WithExportConfig;
+    use std::time::Duration;
+    use tower_service::Service;
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[tokio::test]
+    async fn it_exports_server_span_before_returning() {
+        let (addr, mut exported) = stand_in(|_| ok(&[], "")).await;
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(format!("http://{}/v1/traces", addr))
+            .build()
+            .unwrap();
+
+        let mut service = OtelLayer::new(exporter).layer(service_fn(|_req: Request<Body>| async {
+            Ok::<_, Error>(
+                Response::builder()
+                    .status(StatusCode::OK)
+                    .body(Body::Empty)
+                    .unwrap(),
+            )
+        }));
+
+        let request = Request::builder()
+            .method("GET")
+            .uri("https://example.vercel.app/api/users?page=2")
+            .header(
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            )
+            .body(Body::Empty)
+            .unwrap();
+        service.call(request).await.unwrap();
+
+        // The export has completed by the time the response is returned
+        let export = exported.try_recv().expect("spans were not flushed");
+        assert_eq!(export.path, "/v1/traces");
+        let body = export.body;
+
+        let trace_id = [
+            0x4b, 0xf9, 0x2f, 0x35, 0x77, 0xb3, 0x4d, 0xa6, 0xa3, 0xce, 0x92, 0x9d, 0x0e, 0x0e,
+            0x47, 0x36,
+        ];
+        let parent_span_id = [0x00, 0xf0, 0x67, 0xaa, 0x0b, 0xa9, 0x02, 0xb7];
+        assert!(contains(&body, &trace_id));
+        assert!(contains(&body, &parent_span_id));
+        assert!(contains(&body, b"http.request.method"));
+        assert!(contains(&body, b"/api/users"));
+        assert!(contains(&body, b"http.response.status_code"));
+    }
+
+    #[tokio::test]
+    async fn it_exports_to_https_endpoints() {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(format!(
+                "https://{}/v1/traces",
+                listener.local_addr().unwrap()
+            ))
+            .build()
+            .unwrap();
+
+        let mut service = OtelLayer::new(exporter).layer(service_fn(|_req: Request<Body>| async {
+            Ok::<_, Error>(Response::new(Body::Empty))
+        }));
+        let invocation = tokio::spawn(async move {
+            let request = Request::builder()
+                .uri("https://example.vercel.app/api/users")
+                .body(Body::Empty)
+                .unwrap();
+            service.call(request).await
+        });
+
+        // The exporter opens a TLS session instead of refusing the https scheme
+        let (mut socket, _) = tokio::time::timeout(Duration::from_secs(5), listener.accept())
+            .await
+            .expect("exporter did not connect")
+            .unwrap();
+        let mut record_type = [0u8; 1];
+        socket.read_exact(&mut record_type).await.unwrap();
+        assert_eq!(record_type[0], 0x16, "expected a TLS handshake record");
+
+        drop(socket);
+        invocation.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,554 @@
+use lambda_http::http::{Method, Request, Response, StatusCode};
+use lambda_http::tower::Layer;
+use lambda_runtime_api_client::{build_request, Client};
+use pin_project_lite::pin_project;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+use tower_service::Service;
+use tracing::{debug, error, warn};
+
+use crate::cors::CorsLayer;
+use crate::limits::{BodyLimitLayer, TimeoutLayer};
+use crate::logging::LogLayer;
+use crate::metrics::MetricsLayer;
+use crate::rate_limit::RateLimitLayer;
+use crate::state::{Handler, State};
+use crate::{process_request, process_response, service_fn, Error, ServiceBuilder};
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+type InitHook = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = Result<(), Error>>>>>;
+type ShutdownHook = Box<dyn FnOnce() -> BoxFuture<()> + Send>;
+type AfterResponseHook = Arc<dyn Fn(Invocation) -> BoxFuture<()> + Send + Sync>;
+
+/// Name of the internal extension registered to receive `SIGTERM` before shutdown.
+const EXTENSION_NAME: &str = "vercel-runtime-shutdown";
+
+/// Summary of a handled invocation, passed to [`Runtime::after_response`] hooks.
+#[derive(Debug, Clone)]
+pub struct Invocation {
+    pub method: Method,
+    pub path: String,
+    /// `None` if the handler returned an error
+    pub status: Option<StatusCode>,
+    pub duration: Duration,
+}
+
+/// Builder for running a handler with lifecycle hooks.
+///
+/// ```no_run
+/// # use vercel_runtime::{Body, Error, Request, Response, Runtime};
+/// # async fn handler(_req: Request) -> Result<Response<Body>, Error> { unimplemented!() }
+/// # async fn run() -> Result<(), Error> {
+/// Runtime::new()
+///     .on_init(|| async {
+///         // open connection pools, load configuration, ...
+///         Ok(())
+///     })
+///     .on_shutdown(|| async {
+///         // flush buffered telemetry, close connections, ...
+///     })
+///     .run(handler)
+///     .await
+/// # }
+/// ```
+pub struct Runtime<S = ()> {
+    init: Vec<InitHook>,
+    shutdown: Vec<ShutdownHook>,
+    after_response: Vec<AfterResponseHook>,
+    runtime_api: Option<String>,
+    cors: Option<CorsLayer>,
+    body_limit: BodyLimitLayer,
+    timeout: TimeoutLayer,
+    rate_limit: Option<RateLimitLayer>,
+    metrics: MetricsLayer,
+    state: State<S>,
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Runtime {
+    pub fn new() -> Self {
+        Runtime {
+            init: vec![],
+            shutdown: vec![],
+            after_response: vec![],
+            runtime_api: std::env::var("AWS_LAMBDA_RUNTIME_API").ok(),
+            cors: crate::cors::layer(),
+            body_limit: BodyLimitLayer::from_env(),
+            timeout: TimeoutLayer::from_env(),
+            rate_limit: None,
+            metrics: MetricsLayer::new(),
+            state: State::new(()),
+        }
+    }
+}
+
+impl<S> Runtime<S> {
+    /// Shares `state` with all invocations. Handlers taking a [`State<T>`] as their second
+    /// argument receive a clone of its `Arc`.
+    pub fn with_state<T>(self, state: T) -> Runtime<T> {
+        Runtime {
+            init: self.init,
+            shutdown: self.shutdown,
+            after_response: self.after_response,
+            runtime_api: self.runtime_api,
+            cors: self.cors,
+            body_limit: self.body_limit,
+            timeout: self.timeout,
+            rate_limit: self.rate_limit,
+            metrics: self.metrics,
+            state: State::new(state),
+        }
+    }
+
+    /// Answers preflight requests and adds CORS headers to responses, replacing the
+    /// configuration read from the `CORS_*` environment variables.
+    pub fn cors(mut self, cors: impl Into<CorsLayer>) -> Self {
+        self.cors = Some(cors.into());
+        self
+    }
+
+    /// Answers `413` to requests with a body larger than `limit` bytes, replacing the limit read
+    /// from `VERCEL_BODY_LIMIT`.
+    pub fn body_limit(mut self, limit: usize) -> Self {
+        self.body_limit = BodyLimitLayer::new(limit);
+        self
+    }
+
+    /// Answers `504` if the handler is still running `margin` before the Lambda deadline,
+    /// replacing the margin read from `VERCEL_TIMEOUT_MARGIN_MS`.
+    pub fn timeout_margin(mut self, margin: Duration) -> Self {
+        self.timeout = TimeoutLayer::new(margin);
+        self
+    }
+
+    /// Answers `429` to clients exceeding the layer's limit.
+    pub fn rate_limit(mut self, rate_limit: RateLimitLayer) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    /// Records invocation metrics with `metrics`, e.g. to add a `Server-Timing` header or map
+    /// request paths to the `route` label.
+    pub fn metrics(mut self, metrics: MetricsLayer) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Runs `f` once before the first invocation. If it fails, the error is reported to the
+    /// Lambda runtime API as an initialisation error and [`Runtime::run`] returns it.
+    pub fn on_init<F, Fut>(mut self, f: F) -> Self
+    where
+        F: FnOnce() -> Fut + 'static,
+        Fut: Future<Output = Result<(), Error>> + 'static,
+    {
+        self.init.push(Box::new(move || Box::pin(f())));
+        self
+    }
+
+    /// Runs `f` when the execution environment shuts down.
+    ///
+    /// On Lambda, this registers an internal extension so the runtime receives `SIGTERM`
+    /// before shutdown. Hooks have about 500ms to complete.
+    pub fn on_shutdown<F, Fut>(mut self, f: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.shutdown.push(Box::new(move || Box::pin(f())));
+        self
+    }
+
+    /// Runs `f` after each invocation, once the handler has produced its response.
+    ///
+    /// The response is handed to the runtime when all hooks have completed, as the execution
+    /// environment may be frozen right after.
+    pub fn after_response<F, Fut>(mut self, f: F) -> Self
+    where
+        F: Fn(Invocation) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.after_response
+            .push(Arc::new(move |invocation| Box::pin(f(invocation))));
+        self
+    }
+
+    /// Runs `handler` for every invocation. It may take the [`State`] set with
+    /// [`Runtime::with_state`] as its second argument.
+    pub async fn run<H, M>(self, f: H) -> Result<(), Error>
+    where
+        H: Handler<S, M>,
+    {
+        let Runtime {
+            init,
+            shutdown,
+            after_response,
+            runtime_api,
+            cors,
+            body_limit,
+            timeout,
+            rate_limit,
+            metrics,
+            state,
+        } = self;
+
+        for hook in init {
+            if let Err(e) = hook().await {
+                error!("Initialisation failed: {}", e);
+                if let Some(ref runtime_api) = runtime_api {
+                    if let Err(report_error) = report_init_error(runtime_api, &e).await {
+                        error!("Could not report initialisation error: {}", report_error);
+                    }
+                }
+                return Err(e);
+            }
+        }
+
+        if !shutdown.is_empty() {
+            if let Some(ref runtime_api) = runtime_api {
+                match register_extension(runtime_api).await {
+                    Ok(id) => {
+                        let runtime_api = runtime_api.clone();
+                        tokio::spawn(async move {
+                            while next_extension_event(&runtime_api, &id).await.is_ok() {}
+                        });
+                    }
+                    Err(e) => warn!("Could not register shutdown extension: {}", e),
+                }
+            }
+
+            tokio::spawn(async move {
+                run_on_shutdown(shutdown_signal(), shutdown).await;
+                std::process::exit(0);
+            });
+        }
+
+        // Shared by the clones of the handler's service, e.g. the one called once a request
+        // has passed the rate limit
+        let f = Arc::new(Mutex::new(f));
+        let handler = ServiceBuilder::new()
+            .map_request(process_request)
+            .map_response(process_response)
+            .layer(LogLayer)
+            .option_layer(cors);
+        #[cfg(feature = "otel")]
+        let handler = handler.option_layer(crate::otel::layer());
+        let handler = handler
+            .layer(metrics)
+            .layer(AfterResponseLayer {
+                hooks: after_response.into(),
+            })
+            .layer(body_limit)
+            .option_layer(rate_limit)
+            .layer(timeout)
+            .service(service_fn(move |req| {
+                let mut f = f.lock().expect("handler lock poisoned");
+                f.call(req, &state)
+            }));
+
+        lambda_runtime::run(handler).await
+    }
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sigterm = signal(SignalKind::terminate()).expect("failed to listen for SIGTERM");
+        let mut sigint = signal(SignalKind::interrupt()).expect("failed to listen for SIGINT");
+        tokio::select! {
+            _ = sigterm.recv() => debug!("Received SIGTERM"),
+            _ = sigint.recv() => debug!("Received SIGINT"),
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+async fn run_on_shutdown(signal: impl Future<Output = ()>, hooks: Vec<ShutdownHook>) {
+    signal.await;
+    for hook in hooks {
+        hook().await;
+    }
+}
+
+fn client(runtime_api: &str) -> Result<Client, Error> {
+    Ok(Client::builder()
+        .with_endpoint(format!("http://{}", runtime_api).parse()?)
+        .build()?)
+}
+
+/// Reports a failed initialisation via the runtime API's `/runtime/init/error` endpoint.
+async fn report_init_error(runtime_api: &str, e: &Error) -> Result<(), Error> {
+    let body = serde_json::json!({
+        "errorType": "Runtime.InitError",
+        "errorMessage": e.to_string(),
+    });
+    let req = build_request()
+        .method(Method::POST)
+        .uri("/2018-06-01/runtime/init/error")
+        .header("lambda-runtime-function-error-type", "Runtime.InitError")
+        .body(body.to_string().into())?;
+
+    let res = client(runtime_api)?.call(req).await?;
+    if !res.status().is_success() {
+        return Err(format!("runtime API responded with {}", res.status()).into());
+    }
+    Ok(())
+}
+
+/// Registers an internal extension without events via the extensions API, which makes Lambda
+/// send `SIGTERM` to the runtime before shutting the execution environment down.
+async fn register_extension(runtime_api: &str) -> Result<String, Error> {
+    let req = build_request()
+        .method(Method::POST)
+        .uri("/2020-01-01/extension/register")
+        .header("lambda-extension-name", EXTENSION_NAME)
+        .body(r#"{"events":[]}"#.into())?;
+
+    let res = client(runtime_api)?.call(req).await?;
+    if !res.status().is_success() {
+        return Err(format!("extensions API responded with {}", res.status()).into());
+    }
+
+    let id = res
+        .headers()
+        .get("lambda-extension-identifier")
+        .ok_or("extensions API did not return an extension identifier")?
+        .to_str()?;
+    Ok(id.to_string())
+}
+
+/// Signals the extension is ready and waits for its next event.
+async fn next_extension_event(runtime_api: &str, id: &str) -> Result<(), Error> {
+    let req = build_request()
+        .method(Method::GET)
+        .uri("/2020-01-01/extension/event/next")
+        .header("lambda-extension-identifier", id)
+        .body(lambda_runtime_api_client::body::Body::empty())?;
+
+    let res = client(runtime_api)?.call(req).await?;
+    if !res.status().is_success() {
+        return Err(format!("extensions API responded with {}", res.status()).into());
+    }
+    Ok(())
+}
+
+/// Layer running [`Runtime::after_response`] hooks once the inner service has responded.
+#[derive(Clone)]
+struct AfterResponseLayer {
+    hooks: Arc<[AfterResponseHook]>,
+}
+
+impl<S> Layer<S> for AfterResponseLayer {
+    type Service = AfterResponseService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AfterResponseService {
+            inner,
+            hooks: self.hooks.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct AfterResponseService<S> {
+    inner: S,
+    hooks: Arc<[AfterResponseHook]>,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for AfterResponseService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = AfterResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let method = req.method().clone();
+        let path = req.uri().path().to_string();
+
+        AfterResponseFuture {
+            inner: self.inner.call(req),
+            hooks: self.hooks.clone(),
+            method,
+            path,
+            start: Instant::now(),
+            result: None,
+            pending: None,
+        }
+    }
+}
+
+pin_project! {
+    struct AfterResponseFuture<F: Future> {
+        #[pin]
+        inner: F,
+        hooks: Arc<[AfterResponseHook]>,
+        method: Method,
+        path: String,
+        start: Instant,
+        result: Option<F::Output>,
+        pending: Option<BoxFuture<()>>,
+    }
+}
+
+impl<F, ResBody, E> Future for AfterResponseFuture<F>
+where
+    F: Future<Output = Result<Response<ResBody>, E>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+
+        if this.pending.is_none() {
+            let result = ready!(this.inner.poll(cx));
+
+            let invocation = Invocation {
+                method: this.method.clone(),
+                path: std::mem::take(this.path),
+                status: result.as_ref().ok().map(|r| r.status()),
+                duration: this.start.elapsed(),
+            };
+            let hooks = this.hooks.clone();
+            *this.pending = Some(Box::pin(async move {
+                for hook in hooks.iter() {
+                    hook(invocation.clone()).await;
+                }
+            }));
+            *this.result = Some(result);
+        }
+
+        if let Some(pending) = this.pending.as_mut() {
+            ready!(pending.as_mut().poll(cx));
+        }
+
+        Poll::Ready(this.result.take().expect("polled after completion"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{register_extension, run_on_shutdown, AfterResponseLayer, Invocation, Runtime};
+    use crate::testing::{ok, stand_in};
+    use lambda_http::http::{Method, Request, Response, StatusCode};
+    use lambda_http::tower::Layer;
+    use lambda_http::{service_fn, Body, Error};
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::oneshot;
+    use tower_service::Service;
+
+    #[tokio::test]
+    async fn it_reports_init_errors() {
+        let (addr, mut received) = stand_in(|_| ok(&[], "")).await;
+
+        let mut runtime = Runtime::new().on_init(|| async { Err("database unreachable".into()) });
+        runtime.runtime_api = Some(addr);
+        let result = runtime
+            .run(|_req: Request<Body>| async { Ok(Response::new(Body::Empty)) })
+            .await;
+        assert_eq!(result.unwrap_err().to_string(), "database unreachable");
+
+        let report = received.try_recv().expect("init error was not reported");
+        assert_eq!(report.method, "POST");
+        assert_eq!(report.path, "/2018-06-01/runtime/init/error");
+        assert_eq!(
+            report.headers["lambda-runtime-function-error-type"],
+            "Runtime.InitError"
+        );
+        let body: serde_json::Value = serde_json::from_slice(&report.body).unwrap();
+        assert_eq!(body["errorType"], "Runtime.InitError");
+        assert_eq!(body["errorMessage"], "database unreachable");
+    }
+
+    #[tokio::test]
+    async fn it_registers_shutdown_extension() {
+        let (addr, mut received) =
+            stand_in(|_| ok(&[("lambda-extension-identifier", "ext-1")], "{}")).await;
+
+        assert_eq!(register_extension(&addr).await.unwrap(), "ext-1");
+
+        let registration = received.try_recv().unwrap();
+        assert_eq!(registration.path, "/2020-01-01/extension/register");
+        assert_eq!(
+            registration.headers["lambda-extension-name"],
+            "vercel-runtime-shutdown"
+        );
+        assert_eq!(registration.body, br#"{"events":[]}"#);
+    }
+
+    #[tokio::test]
+    async fn it_runs_shutdown_hooks_on_signal() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let (first, second) = (calls.clone(), calls.clone());
+        let runtime = Runtime::new()
+            .on_shutdown(move || async move { first.lock().unwrap().push("first") })
+            .on_shutdown(move || async move { second.lock().unwrap().push("second") });
+
+        let (tx, rx) = oneshot::channel::<()>();
+        let shutdown = tokio::spawn(run_on_shutdown(
+            async {
+                rx.await.ok();
+            },
+            runtime.shutdown,
+        ));
+        tokio::task::yield_now().await;
+        assert!(calls.lock().unwrap().is_empty());
+
+        tx.send(()).unwrap();
+        shutdown.await.unwrap();
+        assert_eq!(*calls.lock().unwrap(), ["first", "second"]);
+    }
+
+    #[tokio::test]
+    async fn it_runs_after_response_hooks() {
+        let invocations = Arc::new(Mutex::new(Vec::<Invocation>::new()));
+        let recorded = invocations.clone();
+        let runtime = Runtime::new().after_response(move |invocation| {
+            let recorded = recorded.clone();
+            async move { recorded.lock().unwrap().push(invocation) }
+        });
+
+        let mut service = AfterResponseLayer {
+            hooks: runtime.after_response.into(),
+        }
+        .layer(service_fn(|_req: Request<Body>| async {
+            Ok::<_, Error>(
+                Response::builder()
+                    .status(StatusCode::ACCEPTED)
+                    .body(Body::Empty)
+                    .unwrap(),
+            )
+        }));
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("https://example.vercel.app/api/jobs")
+            .body(Body::Empty)
+            .unwrap();
+        service.call(request).await.unwrap();
+
+        let invocations = invocations.lock().unwrap();
+        assert_eq!(invocations.len(), 1);
+        assert_eq!(invocations[0].method, Method::POST);
+        assert_eq!(invocations[0].path, "/api/jobs");
+        assert_eq!(invocations[0].status, Some(StatusCode::ACCEPTED));
+    }
+}
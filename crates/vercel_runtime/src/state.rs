@@ -0,0 +1,117 @@
+use lambda_http::{Body, Error, Request, Response};
+use std::future::Future;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// Application state shared by all invocations, set with [`crate::Runtime::with_state`].
+///
+/// Handlers receive it as their second argument:
+///
+/// ```no_run
+/// # use vercel_runtime::{Body, Error, Request, Response, Runtime, State};
+/// struct AppState {
+///     greeting: String,
+/// }
+///
+/// async fn handler(_req: Request, state: State<AppState>) -> Result<Response<Body>, Error> {
+///     Ok(Response::new(Body::Text(state.greeting.clone())))
+/// }
+///
+/// # async fn run() -> Result<(), Error> {
+/// Runtime::new()
+///     .with_state(AppState {
+///         greeting: "Hello, world!".to_string(),
+///     })
+///     .run(handler)
+///     .await
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct State<T>(pub Arc<T>);
+
+impl<T> State<T> {
+    pub fn new(state: T) -> Self {
+        State(Arc::new(state))
+    }
+}
+
+impl<T> Clone for State<T> {
+    fn clone(&self) -> Self {
+        State(self.0.clone())
+    }
+}
+
+impl<T> Deref for State<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Function handling invocations, taking a [`Request`] and optionally the [`State`] shared
+/// with the other invocations.
+///
+/// `M` only distinguishes the two signatures and is inferred.
+pub trait Handler<S, M> {
+    type Future: Future<Output = Result<Response<Body>, Error>>;
+
+    fn call(&mut self, req: Request, state: &State<S>) -> Self::Future;
+}
+
+impl<S, F, Fut> Handler<S, ()> for F
+where
+    F: FnMut(Request) -> Fut,
+    Fut: Future<Output = Result<Response<Body>, Error>>,
+{
+    type Future = Fut;
+
+    fn call(&mut self, req: Request, _state: &State<S>) -> Self::Future {
+        self(req)
+    }
+}
+
+impl<S, F, Fut> Handler<S, State<S>> for F
+where
+    F: FnMut(Request, State<S>) -> Fut,
+    Fut: Future<Output = Result<Response<Body>, Error>>,
+{
+    type Future = Fut;
+
+    fn call(&mut self, req: Request, state: &State<S>) -> Self::Future {
+        self(req, state.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Handler, State};
+    use lambda_http::{Body, Error, Request, Response};
+
+    async fn plain(_req: Request) -> Result<Response<Body>, Error> {
+        Ok(Response::new(Body::Text("plain".to_string())))
+    }
+
+    async fn stateful(_req: Request, state: State<String>) -> Result<Response<Body>, Error> {
+        Ok(Response::new(Body::Text(state.to_string())))
+    }
+
+    async fn call<M>(mut handler: impl Handler<String, M>, state: &State<String>) -> Body {
+        handler
+            .call(Request::new(Body::Empty), state)
+            .await
+            .unwrap()
+            .into_body()
+    }
+
+    #[tokio::test]
+    async fn it_passes_shared_state_to_handlers() {
+        let state = State::new("shared".to_string());
+
+        assert_eq!(call(plain, &state).await, Body::Text("plain".to_string()));
+        assert_eq!(
+            call(stateful, &state).await,
+            Body::Text("shared".to_string())
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! Minimal HTTP/1.1 server standing in for external endpoints (OTLP collectors, the Lambda
+//! runtime API) in tests.
+
+use std::collections::HashMap;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+#[derive(Debug)]
+pub(crate) struct Recorded {
+    pub method: String,
+    pub path: String,
+    /// Header names are lowercased
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+/// A response with status 200 and the given extra headers and body.
+pub(crate) fn ok(headers: &[(&str, &str)], body: &str) -> String {
+    let headers = headers
+        .iter()
+        .map(|(k, v)| format!("{}: {}\r\n", k, v))
+        .collect::<String>();
+    format!(
+        "HTTP/1.1 200 OK\r\n{}content-length: {}\r\n\r\n{}",
+        headers,
+        body.len(),
+        body
+    )
+}
+
+/// Starts the stand-in, returning its `host:port` and a channel of the requests it received.
+pub(crate) async fn stand_in<F>(respond: F) -> (String, mpsc::UnboundedReceiver<Recorded>)
+where
+    F: Fn(&Recorded) -> String + Send + Sync + Clone + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            tokio::spawn(serve(socket, tx.clone(), respond.clone()));
+        }
+    });
+
+    (addr, rx)
+}
+
+async fn serve<F>(mut socket: TcpStream, tx: mpsc::UnboundedSender<Recorded>, respond: F)
+where
+    F: Fn(&Recorded) -> String,
+{
+    let mut buf = vec![];
+    loop {
+        let head_end = loop {
+            if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                break pos + 4;
+            }
+            let mut chunk = [0; 4096];
+            match socket.read(&mut chunk).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            }
+        };
+
+        let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+        let mut lines = head.lines();
+        let mut request_line = lines.next().unwrap_or_default().split(' ');
+        let method = request_line.next().unwrap_or_default().to_string();
+        let path = request_line.next().unwrap_or_default().to_string();
+        let headers = lines
+            .filter_map(|l| l.split_once(':'))
+            .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_string()))
+            .collect::<HashMap<_, _>>();
+
+        let length = headers
+            .get("content-length")
+            .and_then(|l| l.parse::<usize>().ok())
+            .unwrap_or_default();
+        while buf.len() < head_end + length {
+            let mut chunk = [0; 4096];
+            match socket.read(&mut chunk).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+        let body = buf[head_end..head_end + length].to_vec();
+        buf.drain(..head_end + length);
+
+        let recorded = Recorded {
+            method,
+            path,
+            headers,
+            body,
+        };
+        let response = respond(&recorded);
+        let _ = tx.send(recorded);
+
+        if socket.write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,21 @@
+//! TLS client configuration shared by the JWKS fetcher and the Redis store.
+
+use lambda_http::Error;
+use std::sync::{Arc, OnceLock};
+
+/// Client configuration trusting the platform's root certificates, built once per instance.
+pub(crate) fn client_config() -> Result<Arc<rustls::ClientConfig>, Error> {
+    static CONFIG: OnceLock<Arc<rustls::ClientConfig>> = OnceLock::new();
+    if let Some(config) = CONFIG.get() {
+        return Ok(config.clone());
+    }
+
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in rustls_native_certs::load_native_certs()? {
+        let _ = roots.add(cert);
+    }
+    let config = rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(CONFIG.get_or_init(|| Arc::new(config)).clone())
+}
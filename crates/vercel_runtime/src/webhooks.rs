@@ -0,0 +1,391 @@
+//! Webhook signature verification, enabled by the `webhooks` feature.
+//!
+//! Signatures are checked against the exact bytes of the request body, before any parsing:
+//!
+//! ```no_run
+//! use vercel_runtime::webhooks::{GitHub, VerifiedWebhook};
+//! use vercel_runtime::{Body, Error, Request, Response};
+//!
+//! async fn handler(req: Request) -> Result<Response<Body>, Error> {
+//!     let webhook = match VerifiedWebhook::<GitHub>::from_request(&req) {
+//!         Ok(webhook) => webhook,
+//!         Err(e) => return Ok(e.into_response()),
+//!     };
+//!
+//!     let event: serde_json::Value = webhook.json()?;
+//!     Ok(Response::new(Body::Empty))
+//! }
+//! ```
+
+use lambda_http::http::{header, HeaderMap, Response, StatusCode};
+use lambda_http::{Body, Request};
+use ring::hmac;
+use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{error, warn};
+
+/// How far a signed timestamp may be from the current time.
+pub const TIMESTAMP_TOLERANCE: Duration = Duration::from_secs(5 * 60);
+
+/// Why a webhook was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookError {
+    /// The environment variable holding the secret is not set
+    SecretMissing(&'static str),
+    /// The signature (or timestamp) header is missing or malformed
+    MissingSignature,
+    /// No signature matches the body
+    InvalidSignature,
+    /// The signed timestamp is outside of [`TIMESTAMP_TOLERANCE`]
+    Expired,
+}
+
+impl std::fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebhookError::SecretMissing(env) => write!(f, "{} is not set", env),
+            WebhookError::MissingSignature => write!(f, "missing webhook signature"),
+            WebhookError::InvalidSignature => write!(f, "invalid webhook signature"),
+            WebhookError::Expired => write!(f, "webhook timestamp is out of tolerance"),
+        }
+    }
+}
+
+impl std::error::Error for WebhookError {}
+
+impl WebhookError {
+    /// JSON error response: `500` if the secret is not configured, `401` otherwise.
+    pub fn into_response(self) -> Response<Body> {
+        let (status, code) = match self {
+            WebhookError::SecretMissing(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "webhook_secret_missing")
+            }
+            WebhookError::MissingSignature
+            | WebhookError::InvalidSignature
+            | WebhookError::Expired => (StatusCode::UNAUTHORIZED, "invalid_webhook_signature"),
+        };
+        let body = serde_json::json!({ "message": self.to_string(), "code": code });
+
+        Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::Text(body.to_string()))
+            .expect("valid error response")
+    }
+}
+
+/// A webhook sender and its signature scheme.
+pub trait Provider {
+    /// Environment variable read by [`VerifiedWebhook::from_request`].
+    const SECRET_ENV: &'static str;
+
+    /// Verifies the signature in `headers` over `body`, signed with `secret` around `now`.
+    fn verify(
+        headers: &HeaderMap,
+        body: &[u8],
+        secret: &[u8],
+        now: SystemTime,
+    ) -> Result<(), WebhookError>;
+}
+
+/// GitHub's `X-Hub-Signature-256` HMAC-SHA256 signature.
+#[derive(Debug, Clone, Copy)]
+pub struct GitHub;
+
+impl Provider for GitHub {
+    const SECRET_ENV: &'static str = "GITHUB_WEBHOOK_SECRET";
+
+    fn verify(
+        headers: &HeaderMap,
+        body: &[u8],
+        secret: &[u8],
+        _now: SystemTime,
+    ) -> Result<(), WebhookError> {
+        let signature = header_str(headers, "x-hub-signature-256")?
+            .strip_prefix("sha256=")
+            .ok_or(WebhookError::MissingSignature)?;
+
+        verify_hex(hmac::HMAC_SHA256, secret, body, signature)
+    }
+}
+
+/// Stripe's `Stripe-Signature` header, with a timestamp and one or more HMAC-SHA256 signatures.
+#[derive(Debug, Clone, Copy)]
+pub struct Stripe;
+
+impl Provider for Stripe {
+    const SECRET_ENV: &'static str = "STRIPE_WEBHOOK_SECRET";
+
+    fn verify(
+        headers: &HeaderMap,
+        body: &[u8],
+        secret: &[u8],
+        now: SystemTime,
+    ) -> Result<(), WebhookError> {
+        let mut timestamp = None;
+        let mut signatures = vec![];
+        for pair in header_str(headers, "stripe-signature")?.split(',') {
+            match pair.trim().split_once('=') {
+                Some(("t", t)) => timestamp = Some(t),
+                Some(("v1", signature)) => signatures.push(signature),
+                _ => {}
+            }
+        }
+        let timestamp = timestamp.ok_or(WebhookError::MissingSignature)?;
+        if signatures.is_empty() {
+            return Err(WebhookError::MissingSignature);
+        }
+
+        let payload = [timestamp.as_bytes(), b".", body].concat();
+        signatures
+            .iter()
+            .find_map(|s| verify_hex(hmac::HMAC_SHA256, secret, &payload, s).ok())
+            .ok_or(WebhookError::InvalidSignature)?;
+
+        check_timestamp(timestamp, now)
+    }
+}
+
+/// Slack's `X-Slack-Signature` HMAC-SHA256 signature over `X-Slack-Request-Timestamp` and
+/// the body.
+#[derive(Debug, Clone, Copy)]
+pub struct Slack;
+
+impl Provider for Slack {
+    const SECRET_ENV: &'static str = "SLACK_SIGNING_SECRET";
+
+    fn verify(
+        headers: &HeaderMap,
+        body: &[u8],
+        secret: &[u8],
+        now: SystemTime,
+    ) -> Result<(), WebhookError> {
+        let timestamp = header_str(headers, "x-slack-request-timestamp")?;
+        let signature = header_str(headers, "x-slack-signature")?
+            .strip_prefix("v0=")
+            .ok_or(WebhookError::MissingSignature)?;
+
+        let payload = [b"v0:", timestamp.as_bytes(), b":", body].concat();
+        verify_hex(hmac::HMAC_SHA256, secret, &payload, signature)?;
+
+        check_timestamp(timestamp, now)
+    }
+}
+
+/// Vercel's `x-vercel-signature` HMAC-SHA1 signature, used by integration and account
+/// webhooks.
+#[derive(Debug, Clone, Copy)]
+pub struct Vercel;
+
+impl Provider for Vercel {
+    const SECRET_ENV: &'static str = "VERCEL_WEBHOOK_SECRET";
+
+    fn verify(
+        headers: &HeaderMap,
+        body: &[u8],
+        secret: &[u8],
+        _now: SystemTime,
+    ) -> Result<(), WebhookError> {
+        let signature = header_str(headers, "x-vercel-signature")?;
+
+        verify_hex(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret, body, signature)
+    }
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Result<&'a str, WebhookError> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .ok_or(WebhookError::MissingSignature)
+}
+
+fn verify_hex(
+    algorithm: hmac::Algorithm,
+    secret: &[u8],
+    payload: &[u8],
+    signature: &str,
+) -> Result<(), WebhookError> {
+    let signature = hex::decode(signature.trim()).map_err(|_| WebhookError::InvalidSignature)?;
+    let key = hmac::Key::new(algorithm, secret);
+
+    // Compares in constant time
+    hmac::verify(&key, payload, &signature).map_err(|_| WebhookError::InvalidSignature)
+}
+
+fn check_timestamp(timestamp: &str, now: SystemTime) -> Result<(), WebhookError> {
+    let timestamp = timestamp
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| WebhookError::MissingSignature)?;
+    let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+
+    match now.abs_diff(timestamp) <= TIMESTAMP_TOLERANCE.as_secs() {
+        true => Ok(()),
+        false => Err(WebhookError::Expired),
+    }
+}
+
+/// A request body whose signature has been verified for the provider `P`.
+#[derive(Debug, Clone)]
+pub struct VerifiedWebhook<P> {
+    headers: HeaderMap,
+    body: Vec<u8>,
+    provider: PhantomData<fn() -> P>,
+}
+
+impl<P: Provider> VerifiedWebhook<P> {
+    /// Verifies `req` with the secret read from [`Provider::SECRET_ENV`].
+    pub fn from_request(req: &Request) -> Result<Self, WebhookError> {
+        let secret = std::env::var(P::SECRET_ENV)
+            .ok()
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| {
+                error!("{} is not set, rejecting webhook", P::SECRET_ENV);
+                WebhookError::SecretMissing(P::SECRET_ENV)
+            })?;
+
+        Self::from_request_with_secret(req, secret.as_bytes())
+    }
+
+    pub fn from_request_with_secret(req: &Request, secret: &[u8]) -> Result<Self, WebhookError> {
+        Self::verify(req, secret, SystemTime::now())
+    }
+
+    fn verify(req: &Request, secret: &[u8], now: SystemTime) -> Result<Self, WebhookError> {
+        let body = req.body().as_ref();
+        if let Err(e) = P::verify(req.headers(), body, secret, now) {
+            warn!("Rejected webhook: {}", e);
+            return Err(e);
+        }
+
+        Ok(VerifiedWebhook {
+            headers: req.headers().clone(),
+            body: body.to_vec(),
+            provider: PhantomData,
+        })
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// The verified raw body.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    pub fn into_body(self) -> Vec<u8> {
+        self.body
+    }
+
+    /// Deserializes the verified body as JSON.
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GitHub, Slack, Stripe, Vercel, VerifiedWebhook, WebhookError};
+    use lambda_http::http::StatusCode;
+    use lambda_http::{Body, Request};
+    use ring::hmac;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    const SECRET: &[u8] = b"whsec_test";
+    const BODY: &str = r#"{"action":"opened"}"#;
+
+    fn sign(algorithm: hmac::Algorithm, payload: &[u8]) -> String {
+        hex::encode(hmac::sign(&hmac::Key::new(algorithm, SECRET), payload))
+    }
+
+    fn request(headers: &[(&str, String)], body: &str) -> Request {
+        let mut builder = lambda_http::http::Request::builder().method("POST");
+        for (name, value) in headers {
+            builder = builder.header(*name, value);
+        }
+        builder.body(Body::Text(body.to_string())).unwrap()
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn it_verifies_github_signatures() {
+        let signature = format!("sha256={}", sign(hmac::HMAC_SHA256, BODY.as_bytes()));
+        let req = request(&[("x-hub-signature-256", signature.clone())], BODY);
+        let webhook = VerifiedWebhook::<GitHub>::verify(&req, SECRET, at(0)).unwrap();
+        assert_eq!(webhook.body(), BODY.as_bytes());
+        assert_eq!(
+            webhook.json::<serde_json::Value>().unwrap()["action"],
+            "opened"
+        );
+
+        let tampered = request(
+            &[("x-hub-signature-256", signature)],
+            r#"{"action":"closed"}"#,
+        );
+        assert_eq!(
+            VerifiedWebhook::<GitHub>::verify(&tampered, SECRET, at(0)).unwrap_err(),
+            WebhookError::InvalidSignature
+        );
+        assert_eq!(
+            VerifiedWebhook::<GitHub>::verify(&request(&[], BODY), SECRET, at(0)).unwrap_err(),
+            WebhookError::MissingSignature
+        );
+    }
+
+    #[test]
+    fn it_verifies_stripe_signatures_and_timestamps() {
+        let payload = format!("1700000000.{}", BODY);
+        let signature = format!(
+            "t=1700000000,v1={},v1={}",
+            "00".repeat(32),
+            sign(hmac::HMAC_SHA256, payload.as_bytes())
+        );
+        let req = request(&[("stripe-signature", signature)], BODY);
+
+        assert!(VerifiedWebhook::<Stripe>::verify(&req, SECRET, at(1_700_000_100)).is_ok());
+        assert_eq!(
+            VerifiedWebhook::<Stripe>::verify(&req, SECRET, at(1_700_000_301)).unwrap_err(),
+            WebhookError::Expired
+        );
+        assert_eq!(
+            VerifiedWebhook::<Stripe>::verify(&req, b"other", at(1_700_000_000)).unwrap_err(),
+            WebhookError::InvalidSignature
+        );
+    }
+
+    #[test]
+    fn it_verifies_slack_signatures_and_timestamps() {
+        let payload = format!("v0:1700000000:{}", BODY);
+        let signature = format!("v0={}", sign(hmac::HMAC_SHA256, payload.as_bytes()));
+        let req = request(
+            &[
+                ("x-slack-request-timestamp", "1700000000".to_string()),
+                ("x-slack-signature", signature),
+            ],
+            BODY,
+        );
+
+        assert!(VerifiedWebhook::<Slack>::verify(&req, SECRET, at(1_699_999_900)).is_ok());
+        assert_eq!(
+            VerifiedWebhook::<Slack>::verify(&req, SECRET, at(1_699_999_000)).unwrap_err(),
+            WebhookError::Expired
+        );
+    }
+
+    #[test]
+    fn it_verifies_vercel_signatures() {
+        let signature = sign(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, BODY.as_bytes());
+        let req = request(&[("x-vercel-signature", signature)], BODY);
+        assert!(VerifiedWebhook::<Vercel>::verify(&req, SECRET, at(0)).is_ok());
+
+        let req = request(&[("x-vercel-signature", "not hex".to_string())], BODY);
+        let error = VerifiedWebhook::<Vercel>::verify(&req, SECRET, at(0)).unwrap_err();
+        assert_eq!(error, WebhookError::InvalidSignature);
+        assert_eq!(error.into_response().status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -1,73 +1,127 @@
-use glob::glob;
 use proc_macro::TokenStream;
-use quote::quote;
-use std::collections::HashMap;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use syn::parse_macro_input;
-use syn::AttributeArgs;
-use vercel_runtime_router::{Route, Router};
+use vercel_runtime_router::bundled::Args;
+use vercel_runtime_router::{is_route_file, Route, Router};
 
+/// Serves every route file below `api`, or the `dir` argument, from the annotated handler.
+///
+/// Changed and removed routes recompile the crate, but added ones only do with a build script
+/// calling `vercel_runtime_router::track_routes` on the same directory, and
+/// `vercel_runtime_router` as a build-dependency:
+///
+/// ```toml
+/// [build-dependencies]
+/// vercel_runtime_router = "1"
+/// ```
+///
+/// ```ignore
+/// // build.rs
+/// fn main() {
+///     vercel_runtime_router::track_routes("api");
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn bundled_api(args: TokenStream, stream: TokenStream) -> TokenStream {
-    let args = parse_macro_input!(args as AttributeArgs);
-    let mut args_map: HashMap<String, String> = HashMap::new();
+    let args = parse_macro_input!(args as Args);
+    let input = parse_macro_input!(stream as syn::ItemFn);
 
-    args.iter().for_each(|arg| {
-        if let syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
-            path,
-            lit: syn::Lit::Str(lit_str),
-            ..
-        })) = arg
-        {
-            if let Some(key) = path.get_ident() {
-                args_map.insert(key.to_string(), lit_str.value());
-            }
-        }
-    });
+    // Routes are found relative to the crate's Cargo.toml, whether or not it is built as part of
+    // a workspace
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let root = std::env::var_os("CARGO_MANIFEST_DIR").map_or_else(|| cwd.clone(), PathBuf::from);
+    let config = match args.resolve(&root, &cwd) {
+        Ok(config) => config,
+        Err(e) => return e.to_compile_error().into(),
+    };
 
-    let prefix = args_map
-        .get("path")
-        .map(|p| {
-            if p.ends_with('/') {
-                p.to_owned()
-            } else {
-                format!("{}/", p)
-            }
-        })
-        .and_then(|p| {
-            if fs::metadata(format!("{}api/main.rs", p)).is_ok() {
-                Some(p)
-            } else {
-                // there is a `path` specified, but it doesn't appear to be in a cargo workspace,
-                // so default to acting as if it is not in a cargo workspace.
-                // This protects us from both
-                // - using `vercel` to deploy via build container, but only uploading part of a
-                // workspace
-                // - path being specified incorrectly
-                None
+    let discovered = config.files();
+
+    // Rebuilds the crate once a discovered file changes or is removed. Only a build script can
+    // also track added files, see `vercel_runtime_router::track_routes`
+    let tracked = discovered
+        .iter()
+        .map(|f| {
+            let path = config.path(f).to_string_lossy().into_owned();
+            quote! {
+                const _: &[u8] = include_bytes!(#path);
             }
         })
-        .unwrap_or("".to_string());
+        .collect::<Vec<_>>();
 
-    let glob_pattern = format!("{}api/**/*.rs", prefix);
-    let main_path = PathBuf::from(format!("{}api/main.rs", prefix));
+    // The router sees the files below the base path, e.g. `api/users/[id].rs`
+    let files = discovered
+        .iter()
+        // Private modules like `api/_utils.rs` are included by the routes themselves
+        .filter(|f| is_route_file(f))
+        .map(|f| (config.route_file(f), config.path(f)))
+        .collect::<BTreeMap<_, _>>();
 
-    let input: syn::ItemFn = syn::parse(stream).unwrap();
+    let (middleware_files, raw_routes): (Vec<_>, Vec<_>) = files
+        .keys()
+        .map(String::as_str)
+        .partition(|f| f.rsplit('/').next() == Some(MIDDLEWARE_FILE));
 
-    let raw_routes = glob(&glob_pattern)
-        .expect("a valid glob pattern")
-        .filter_map(|e| e.ok())
-        .filter(|raw_path| raw_path != &main_path)
-        .map(|raw_path| raw_path.to_str().unwrap().to_owned())
+    // `middleware.rs` files apply to the routes in their directory and below, outermost first
+    let mut middleware = middleware_files
+        .into_iter()
+        .map(|f| (f.strip_suffix(MIDDLEWARE_FILE).unwrap(), Route::from(f)))
         .collect::<Vec<_>>();
+    middleware.sort_by_key(|(dir, _)| dir.len());
+    let global_middleware = config.middleware;
 
-    let raw_routes = raw_routes
+    // A route named `middleware.rs` would silently stop being served
+    if let Some((_, m)) = middleware
         .iter()
-        .map(|f| f.strip_prefix(&prefix).unwrap())
-        .collect::<Vec<_>>();
+        .find(|(_, m)| !defines_middleware(&files[&m.module_file]))
+    {
+        let message = format!(
+            "`{}` is reserved for middleware but has no `middleware` function, add one or rename \
+             the file",
+            relative(&files[&m.module_file], &root)
+        );
+        return syn::Error::new_spanned(&input.sig, message)
+            .to_compile_error()
+            .into();
+    }
 
     let router = Router::from(raw_routes);
+    if let Some((first, second)) = router.conflicts().first() {
+        let message = format!(
+            "`{}` and `{}` are both served at `/{}`, remove one of them",
+            relative(&files[&first.module_file], &root),
+            relative(&files[&second.module_file], &root),
+            first.path
+        );
+        return syn::Error::new_spanned(&input.sig, message)
+            .to_compile_error()
+            .into();
+    }
+
+    // Module names replace every character which can't be part of an identifier with `_`
+    let mut modules = BTreeMap::new();
+    for route in router
+        .routes
+        .iter()
+        .chain(middleware.iter().map(|(_, m)| m))
+    {
+        let file = &route.module_file;
+        if let Some(other) = modules.insert(route.module_name.to_string(), file) {
+            let message = format!(
+                "`{}` and `{}` both become the module `{}`, rename one of them",
+                relative(&files[other], &root),
+                relative(&files[file], &root),
+                route.module_name
+            );
+            return syn::Error::new_spanned(&input.sig, message)
+                .to_compile_error()
+                .into();
+        }
+    }
 
     let router_path_tokens = router.routes.iter().map(|r| {
         let Route { module_file, .. } = r;
@@ -77,42 +131,124 @@ pub fn bundled_api(args: TokenStream, stream: TokenStream) -> TokenStream {
         }
     });
 
-    let mod_statements = router.routes.iter().map(|r| {
+    let mod_statements = router
+        .routes
+        .iter()
+        .chain(middleware.iter().map(|(_, m)| m))
+        .map(|r| {
+            let Route {
+                module_name,
+                module_file,
+                ..
+            } = r;
+
+            let path = files[module_file].to_string_lossy().into_owned();
+            quote! {
+                #[path = #path]
+                mod #module_name;
+            }
+        });
+
+    // `routes::<module_name>(params)` builds the URL of a route, requiring all of its parameters
+    let url_fns = router.routes.iter().map(|r| {
         let Route {
             module_name,
             module_file,
+            path,
             ..
         } = r;
 
-        let module_file = format!("../{}", module_file);
+        let (args, params): (Vec<_>, Vec<_>) = path.split('/').filter_map(url_param).unzip();
+        let doc = format!("URL of `{}`", module_file);
         quote! {
-            #[path = #module_file]
-            mod #module_name;
+            #[doc = #doc]
+            pub fn #module_name(#(#args),*) -> Result<String, vercel_runtime::UrlError> {
+                vercel_runtime::Route::from(#module_file).url(&[#(#params),*])
+            }
         }
     });
 
+    let syn::ItemFn {
+        attrs, vis, sig, ..
+    } = input;
+
+    // A second argument holds the `State` shared with routes whose handlers take it
+    let state = match sig.inputs.iter().nth(1) {
+        Some(syn::FnArg::Typed(syn::PatType { pat, .. })) => match pat.as_ref() {
+            syn::Pat::Ident(syn::PatIdent { ident, .. }) => Some(ident.clone()),
+            pat => {
+                return syn::Error::new_spanned(
+                    pat,
+                    "expected the state argument to be an identifier",
+                )
+                .to_compile_error()
+                .into()
+            }
+        },
+        _ => None,
+    };
+
     let matches = router.routes.iter().map(|r| {
         let Route {
             module_name,
             module_file,
             ..
         } = r;
+
+        let call = match (takes_state(&files[module_file]), &state) {
+            (true, Some(state)) => quote! { #module_name::handler(req, #state.clone()) },
+            (true, None) => {
+                let message = format!(
+                    "`{}` takes a `State`, add a state argument to the bundled handler",
+                    module_file
+                );
+                quote! { compile_error!(#message) }
+            }
+            (false, _) => quote! { #module_name::handler(req) },
+        };
+
+        let chain = global_middleware
+            .iter()
+            .map(|path| quote! { #path })
+            .chain(
+                middleware
+                    .iter()
+                    .filter(|(dir, _)| module_file.starts_with(dir))
+                    .map(|(_, m)| {
+                        let module_name = &m.module_name;
+                        quote! { #module_name::middleware }
+                    }),
+            )
+            .collect::<Vec<_>>();
+
+        let Some((outermost, inner)) = chain.split_first() else {
+            return quote! {
+                #module_file => #call.await,
+            };
+        };
+
+        let inner = inner.iter().rev();
         quote! {
             #module_file => {
-                return #module_name::handler(req).await;
+                let next = vercel_runtime::middleware::Next::new(move |req| #call);
+                #(let next = vercel_runtime::middleware::Next::new(move |req| #inner(req, next));)*
+                #outermost(req, next).await
             }
         }
     });
 
-    let syn::ItemFn {
-        attrs, vis, sig, ..
-    } = input;
-
     quote! {
         use vercel_runtime::{Route, Router};
 
+        #(#tracked)*
+
         #(#mod_statements)*
 
+        #[allow(dead_code)]
+        pub mod routes {
+            #(#url_fns)*
+        }
+
         #(#attrs)* #vis #sig {
             let raw_routes = vec![#(#router_path_tokens)*];
             let router = Router::from(raw_routes);
@@ -122,14 +258,204 @@ pub fn bundled_api(args: TokenStream, stream: TokenStream) -> TokenStream {
 
             match router.call(&request_uri) {
                 Some(route) => {
-                    match route.module_file.as_str() {
+                    let mut req = req;
+                    if let Some(params) = route.params(request_uri) {
+                        req.extensions_mut().insert(params);
+                    }
+
+                    let response = match route.module_file.as_str() {
                         #(#matches)*
                         _ => unreachable!()
-                    }
+                    };
+                    response.map(|mut response| {
+                        let route = vercel_runtime::metrics::MatchedRoute(route.module_file.clone());
+                        response.extensions_mut().insert(route);
+                        response
+                    })
+                }
+                // e.g. a parameter not satisfying its constraint
+                None => {
+                    return Ok(vercel_runtime::Response::builder()
+                        .status(vercel_runtime::StatusCode::NOT_FOUND)
+                        .body(vercel_runtime::Body::Empty)?);
                 }
-                None => unreachable!(),
             }
         }
     }
     .into()
 }
+
+const MIDDLEWARE_FILE: &str = "middleware.rs";
+
+/// The argument of a route's URL function for a parameter `segment`, and the `(name, value)` it
+/// passes to `Route::url`. Numeric constraints take the number, optional catch-alls an `Option`.
+fn url_param(segment: &str) -> Option<(TokenStream2, TokenStream2)> {
+    let param = segment.strip_prefix('[')?.strip_suffix(']')?;
+    let (param, optional) = match param.strip_prefix('[') {
+        Some(param) => (param.strip_suffix(']')?, true),
+        None => (param, false),
+    };
+    let param = param.trim_start_matches("...");
+    let (name, constraint) = match param.split_once('=') {
+        Some((name, constraint)) => (name, Some(constraint)),
+        None => (param, None),
+    };
+
+    let ident = name.replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+    let ident = match ident.starts_with(|c: char| c.is_ascii_digit()) {
+        true => format!("_{}", ident),
+        false => ident,
+    };
+    let ident = syn::parse_str::<syn::Ident>(&ident)
+        // e.g. `[type]`
+        .or_else(|_| syn::parse_str::<syn::Ident>(&format!("r#{}", ident)))
+        // `self`, `super` and `crate` can't be raw identifiers
+        .unwrap_or_else(|_| format_ident!("{}_", ident));
+    Some(match (optional, constraint) {
+        (true, _) => (
+            quote! { #ident: Option<&str> },
+            quote! { (#name, #ident.unwrap_or_default()) },
+        ),
+        (false, Some(ty @ ("u32" | "u64" | "i32" | "i64"))) => {
+            let ty = format_ident!("{}", ty);
+            (
+                quote! { #ident: #ty },
+                quote! { (#name, #ident.to_string().as_str()) },
+            )
+        }
+        (false, _) => (quote! { #ident: &str }, quote! { (#name, #ident) }),
+    })
+}
+
+/// `path` relative to the crate's directory `root`, e.g. `api/users.rs`.
+fn relative(path: &Path, root: &Path) -> String {
+    let path = path.strip_prefix(root).unwrap_or(path);
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Whether the `handler` function of the route at `path` takes a second (state) argument.
+fn takes_state(path: &Path) -> bool {
+    let Some(file) = fs::read_to_string(path)
+        .ok()
+        .and_then(|source| syn::parse_file(&source).ok())
+    else {
+        return false;
+    };
+
+    file.items.iter().any(|item| match item {
+        syn::Item::Fn(f) => f.sig.ident == "handler" && f.sig.inputs.len() > 1,
+        _ => false,
+    })
+}
+
+/// Whether the file at `path` defines a `middleware` function. Files which can't be parsed are
+/// left to the compiler to report.
+fn defines_middleware(path: &Path) -> bool {
+    let Some(file) = fs::read_to_string(path)
+        .ok()
+        .and_then(|source| syn::parse_file(&source).ok())
+    else {
+        return true;
+    };
+
+    file.items.iter().any(|item| match item {
+        syn::Item::Fn(f) => f.sig.ident == "middleware",
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    const FIXTURE: &str = "test/fixtures/08-with-bundled-api";
+
+    fn repo() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../..")
+            .canonicalize()
+            .unwrap()
+    }
+
+    fn copy_dir(from: &Path, to: &Path) {
+        std::fs::create_dir_all(to).unwrap();
+        for entry in std::fs::read_dir(from).unwrap() {
+            let entry = entry.unwrap();
+            match entry.file_type().unwrap().is_dir() {
+                true => copy_dir(&entry.path(), &to.join(entry.file_name())),
+                false => {
+                    std::fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+                }
+            }
+        }
+    }
+
+    /// Copies the fixture to a temporary directory, depending on the crates of this checkout.
+    fn fixture() -> PathBuf {
+        let root = std::env::temp_dir().join(format!("bundled-api-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        copy_dir(&repo().join(FIXTURE), &root);
+
+        let manifest = std::fs::read_to_string(root.join("Cargo.toml")).unwrap();
+        let crates = repo().join("crates");
+        let manifest = manifest.replace("../../../crates", crates.to_str().unwrap());
+        std::fs::write(root.join("Cargo.toml"), manifest).unwrap();
+        // Reuse the resolved dependencies of the workspace, if any
+        let _ = std::fs::copy(repo().join("Cargo.lock"), root.join("Cargo.lock"));
+        root
+    }
+
+    /// Builds the fixture, returning the response of its handler to a request for `path`.
+    fn build_and_serve(root: &Path, path: &str) -> String {
+        let target = repo().join("target/fixtures");
+        let status = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()))
+            .args(["build", "--quiet", "--manifest-path"])
+            .arg(root.join("Cargo.toml"))
+            .env("CARGO_TARGET_DIR", &target)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let output = Command::new(target.join("debug/main"))
+            .arg(path)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    #[test]
+    fn it_requires_a_middleware_function() {
+        let dir = std::env::temp_dir().join(format!("middleware-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (middleware, route) = (dir.join("middleware.rs"), dir.join("route.rs"));
+        std::fs::write(
+            &middleware,
+            "pub async fn middleware(req: Request, next: Next) {}",
+        )
+        .unwrap();
+        std::fs::write(&route, "pub async fn handler(req: Request) {}").unwrap();
+
+        assert!(super::defines_middleware(&middleware));
+        assert!(!super::defines_middleware(&route));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Builds a copy of the fixture with cargo, reusing its target directory across runs.
+    #[test]
+    fn it_picks_up_added_and_removed_routes() {
+        let root = fixture();
+        assert_eq!(build_and_serve(&root, "/api/hello"), "200 hello endpoint");
+        assert_eq!(build_and_serve(&root, "/api/added"), "404");
+
+        let hello = std::fs::read_to_string(root.join("api/hello.rs")).unwrap();
+        std::fs::write(root.join("api/added.rs"), hello.replace("hello", "added")).unwrap();
+        assert_eq!(build_and_serve(&root, "/api/added"), "200 added endpoint");
+
+        std::fs::remove_file(root.join("api/added.rs")).unwrap();
+        assert_eq!(build_and_serve(&root, "/api/added"), "404");
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
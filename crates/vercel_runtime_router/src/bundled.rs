@@ -0,0 +1,433 @@
+//! Arguments of `bundled_api`, e.g. `#[bundled_api(dir = "routes", base_path = "/api")]`.
+//!
+//! Shared by the macro and `cargo vercel`, so both find the same routes.
+
+use glob::{MatchOptions, Pattern};
+use proc_macro2::Span;
+use std::path::{Path, PathBuf};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Expr, ExprLit, Lit, LitStr, MetaNameValue, Token};
+
+const DEFAULT_DIR: &str = "api";
+const DEFAULT_INCLUDE: &str = "**/*.rs";
+const ENTRY_FILE: &str = "main.rs";
+
+#[derive(Default)]
+pub struct Args {
+    /// Directory of the crate relative to its workspace, no longer needed
+    path: Option<LitStr>,
+    /// Directory holding the routes, relative to the crate
+    dir: Option<LitStr>,
+    /// Globs of the route files, relative to `dir`
+    include: Vec<LitStr>,
+    /// Globs of the files in `dir` that are not routes, relative to `dir`
+    exclude: Vec<LitStr>,
+    /// URL path the routes are served below, `/<dir>` by default
+    base_path: Option<LitStr>,
+    /// File holding the bundled handler, relative to the crate
+    entry: Option<LitStr>,
+    /// Middleware wrapping every route
+    middleware: Option<syn::Path>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = Args::default();
+        for arg in Punctuated::<MetaNameValue, Token![,]>::parse_terminated(input)? {
+            let name = arg
+                .path
+                .get_ident()
+                .ok_or_else(|| syn::Error::new_spanned(&arg.path, "expected an argument name"))?
+                .to_string();
+            let duplicate = match name.as_str() {
+                "path" => args.path.replace(string(&arg.value)?).is_some(),
+                "dir" => args.dir.replace(string(&arg.value)?).is_some(),
+                "base_path" => args.base_path.replace(string(&arg.value)?).is_some(),
+                "entry" => args.entry.replace(string(&arg.value)?).is_some(),
+                "include" => {
+                    let had = !args.include.is_empty();
+                    args.include = strings(&arg.value)?;
+                    had
+                }
+                "exclude" => {
+                    let had = !args.exclude.is_empty();
+                    args.exclude = strings(&arg.value)?;
+                    had
+                }
+                "middleware" => {
+                    let path = match &arg.value {
+                        Expr::Path(path) => path.path.clone(),
+                        value => string(value)?.parse().map_err(|e| {
+                            syn::Error::new(e.span(), format!("invalid `middleware` path: {}", e))
+                        })?,
+                    };
+                    args.middleware.replace(path).is_some()
+                }
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        &arg.path,
+                        format!(
+                            "unknown argument `{}`, expected one of `dir`, `include`, `exclude`, \
+                             `base_path`, `entry`, `middleware` or `path`",
+                            name
+                        ),
+                    ))
+                }
+            };
+            if duplicate {
+                return Err(syn::Error::new_spanned(
+                    &arg.path,
+                    format!("`{}` is given more than once", name),
+                ));
+            }
+        }
+        Ok(args)
+    }
+}
+
+fn string(value: &Expr) -> syn::Result<LitStr> {
+    match value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(lit), ..
+        }) => Ok(lit.clone()),
+        value => Err(syn::Error::new_spanned(value, "expected a string")),
+    }
+}
+
+/// A string, or an array of strings like `["a/**", "b/**"]`.
+fn strings(value: &Expr) -> syn::Result<Vec<LitStr>> {
+    match value {
+        Expr::Array(array) => array.elems.iter().map(string).collect(),
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(lit), ..
+        }) => Ok(vec![lit.clone()]),
+        value => Err(syn::Error::new_spanned(
+            value,
+            "expected a string or an array of strings",
+        )),
+    }
+}
+
+/// Where `bundled_api` finds the routes, checked against the file system.
+pub struct Config {
+    /// Directory of the routes, e.g. `/project/api`
+    dir: PathBuf,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    /// `base_path` without leading and trailing slashes, e.g. `api`
+    base_path: String,
+    /// The canonical path of the entry file, which is not a route
+    entry: PathBuf,
+    pub middleware: Option<syn::Path>,
+}
+
+impl Args {
+    /// Resolves the arguments for the crate in `root` compiled from `cwd`, which is the
+    /// workspace's directory when the crate is a member of one.
+    pub fn resolve(self, root: &Path, cwd: &Path) -> syn::Result<Config> {
+        let canonical = |path: &Path| path.canonicalize().ok();
+
+        if let Some(path) = self.path {
+            let in_workspace = canonical(cwd) != canonical(root);
+            if in_workspace && canonical(&cwd.join(path.value())) != canonical(root) {
+                return Err(syn::Error::new(
+                    path.span(),
+                    format!(
+                        "`{}` is not the directory of this crate, remove `path` since routes are \
+                         found relative to its Cargo.toml",
+                        path.value()
+                    ),
+                ));
+            }
+        }
+
+        let dir_name = self.dir.as_ref().map_or(DEFAULT_DIR.to_string(), |d| {
+            d.value().trim_end_matches('/').to_string()
+        });
+        let dir = root.join(&dir_name);
+        if !dir.is_dir() {
+            return Err(syn::Error::new(
+                span(&self.dir),
+                format!("`{}` is not a directory of this crate", dir_name),
+            ));
+        }
+
+        let globs = |globs: &[LitStr]| {
+            globs
+                .iter()
+                .map(|g| {
+                    Pattern::new(&g.value()).map_err(|e| {
+                        syn::Error::new(g.span(), format!("invalid glob `{}`: {}", g.value(), e))
+                    })
+                })
+                .collect::<syn::Result<Vec<_>>>()
+        };
+        let include = match self.include.is_empty() {
+            true => vec![Pattern::new(DEFAULT_INCLUDE).unwrap()],
+            false => globs(&self.include)?,
+        };
+        let exclude = globs(&self.exclude)?;
+
+        let base_path = match self.base_path {
+            Some(ref base_path) if !base_path.value().starts_with('/') => {
+                return Err(syn::Error::new(
+                    base_path.span(),
+                    format!(
+                        "`base_path` must start with `/`, e.g. `/{}`",
+                        base_path.value()
+                    ),
+                ))
+            }
+            Some(ref base_path) => base_path.value().trim_matches('/').to_string(),
+            None => dir_name.trim_start_matches("./").to_string(),
+        };
+        // The base path is part of the URLs and the names of the route modules
+        if let Some(c) = base_path
+            .chars()
+            .find(|c| !c.is_ascii_alphanumeric() && !"/-._~".contains(*c))
+        {
+            let (arg, span) = match self.base_path {
+                Some(ref base_path) => ("base_path", base_path.span()),
+                None => ("dir", span(&self.dir)),
+            };
+            return Err(syn::Error::new(
+                span,
+                format!(
+                    "`{}` may only contain letters, digits and `/-._~`, found {:?}",
+                    arg, c
+                ),
+            ));
+        }
+
+        let entry_name = self
+            .entry
+            .as_ref()
+            .map_or(format!("{}/{}", dir_name, ENTRY_FILE), LitStr::value);
+        let entry = canonical(&root.join(&entry_name))
+            .filter(|entry| entry.is_file())
+            .ok_or_else(|| {
+                syn::Error::new(
+                    span(&self.entry),
+                    format!(
+                        "`{}` does not exist, set `entry` to the file holding the bundled handler",
+                        entry_name
+                    ),
+                )
+            })?;
+
+        Ok(Config {
+            dir,
+            include,
+            exclude,
+            base_path,
+            entry,
+            middleware: self.middleware,
+        })
+    }
+}
+
+/// Span of an argument, or of the attribute if it was not given.
+fn span(arg: &Option<LitStr>) -> Span {
+    arg.as_ref().map_or_else(Span::call_site, LitStr::span)
+}
+
+impl Config {
+    /// The Rust files of the routes directory matching `include` but not `exclude`, relative to
+    /// it and sorted, e.g. `users/[id].rs`. The entry is left out.
+    pub fn files(&self) -> Vec<String> {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        let dir = Pattern::escape(&self.dir.to_string_lossy());
+
+        let mut files = vec![];
+        for include in &self.include {
+            let paths = glob::glob(&format!("{}/{}", dir, include.as_str()))
+                .expect("a valid glob pattern")
+                .filter_map(|e| e.ok());
+            for path in paths {
+                if !path.is_file() || path.extension().is_none_or(|e| e != "rs") {
+                    continue;
+                }
+                if path.canonicalize().ok().as_deref() == Some(self.entry.as_path()) {
+                    continue;
+                }
+                let Ok(file) = path.strip_prefix(&self.dir) else {
+                    continue;
+                };
+                if self
+                    .exclude
+                    .iter()
+                    .any(|e| e.matches_path_with(file, options))
+                {
+                    continue;
+                }
+                files.push(file.to_string_lossy().replace('\\', "/"));
+            }
+        }
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    /// `file` below the base path, which is how the router sees it, e.g. `api/users/[id].rs`.
+    pub fn route_file(&self, file: &str) -> String {
+        match self.base_path.as_str() {
+            "" => file.to_string(),
+            base_path => format!("{}/{}", base_path, file),
+        }
+    }
+
+    /// The path of `file` on disk, e.g. `/project/api/users/[id].rs`.
+    pub fn path(&self, file: &str) -> PathBuf {
+        self.dir.join(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Args;
+    use std::path::{Path, PathBuf};
+
+    fn crate_dir(name: &str, files: &[&str]) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("bundled-api-args-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for file in files {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        root.canonicalize().unwrap()
+    }
+
+    fn parse(args: &str) -> syn::Result<Args> {
+        syn::parse_str(args)
+    }
+
+    fn error(root: &Path, args: &str) -> String {
+        match parse(args).and_then(|args| args.resolve(root, root)) {
+            Ok(_) => panic!("expected an error for {}", args),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn it_rejects_invalid_arguments() {
+        let error = |args| parse(args).err().map(|e| e.to_string());
+        assert_eq!(
+            error(r#"dir = "api", directory = "api""#).unwrap(),
+            "unknown argument `directory`, expected one of `dir`, `include`, `exclude`, \
+             `base_path`, `entry`, `middleware` or `path`"
+        );
+        assert_eq!(error("dir = 42").unwrap(), "expected a string");
+        assert_eq!(
+            error(r#"dir = "api", dir = "routes""#).unwrap(),
+            "`dir` is given more than once"
+        );
+        assert_eq!(
+            error("exclude = 42").unwrap(),
+            "expected a string or an array of strings"
+        );
+        assert!(error(r#"middleware = "crate::"#).is_some());
+        assert!(error(r#"middleware = crate::auth, include = ["a/**", "b/**"]"#).is_none());
+    }
+
+    #[test]
+    fn it_checks_arguments_against_the_crate() {
+        let root = crate_dir(
+            "check",
+            &["api/main.rs", "routes/users.rs", "my routes/main.rs"],
+        );
+
+        assert_eq!(
+            error(&root, r#"dir = "handlers""#),
+            "`handlers` is not a directory of this crate"
+        );
+        assert_eq!(
+            error(&root, r#"dir = "routes""#),
+            "`routes/main.rs` does not exist, set `entry` to the file holding the bundled handler"
+        );
+        assert_eq!(
+            error(&root, r#"include = "[a""#),
+            "invalid glob `[a`: Pattern syntax error near position 0: invalid range pattern"
+        );
+        assert_eq!(
+            error(&root, r#"base_path = "v1""#),
+            "`base_path` must start with `/`, e.g. `/v1`"
+        );
+        assert_eq!(
+            error(&root, r#"base_path = "/v1 beta""#),
+            "`base_path` may only contain letters, digits and `/-._~`, found ' '"
+        );
+        assert_eq!(
+            error(&root, r#"dir = "my routes""#),
+            "`dir` may only contain letters, digits and `/-._~`, found ' '"
+        );
+        assert!(parse(r#"dir = "my routes", base_path = "/api""#)
+            .unwrap()
+            .resolve(&root, &root)
+            .is_ok());
+
+        let workspace = root.parent().unwrap();
+        let path = |path: &str| {
+            parse(&format!("path = {:?}", path))
+                .unwrap()
+                .resolve(&root, workspace)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        };
+        assert!(path(root.file_name().unwrap().to_str().unwrap()).is_ok());
+        assert!(path("examples/route-merge").is_err());
+        // Compiling the crate outside of its workspace
+        assert!(parse(r#"path = "examples/route-merge""#)
+            .unwrap()
+            .resolve(&root, &root)
+            .is_ok());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn it_finds_the_routes() {
+        let root = crate_dir(
+            "files",
+            &[
+                "api/main.rs",
+                "api/foo.rs",
+                "api/users/[id].rs",
+                "api/legacy/bar.rs",
+                "api/README.md",
+                "src/handlers/index.rs",
+                "src/handlers/posts.rs",
+                "src/bin/server.rs",
+            ],
+        );
+
+        let config = parse(r#"exclude = "legacy/**""#)
+            .unwrap()
+            .resolve(&root, &root)
+            .unwrap();
+        assert_eq!(config.files(), ["foo.rs", "users/[id].rs"]);
+        assert_eq!(config.route_file("users/[id].rs"), "api/users/[id].rs");
+        assert_eq!(config.path("foo.rs"), root.join("api/foo.rs"));
+
+        let config = parse(r#"dir = "src/handlers", base_path = "/", entry = "src/bin/server.rs""#)
+            .unwrap()
+            .resolve(&root, &root)
+            .unwrap();
+        assert_eq!(config.files(), ["index.rs", "posts.rs"]);
+        assert_eq!(config.route_file("posts.rs"), "posts.rs");
+
+        let config = parse(r#"include = ["users/*.rs", "foo.rs"], base_path = "/v1.0/""#)
+            .unwrap()
+            .resolve(&root, &root)
+            .unwrap();
+        assert_eq!(config.files(), ["foo.rs", "users/[id].rs"]);
+        assert_eq!(config.route_file("foo.rs"), "v1.0/foo.rs");
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
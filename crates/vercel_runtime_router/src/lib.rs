@@ -1,19 +1,80 @@
 use glob::glob;
 use lazy_static::lazy_static;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
 use quote::format_ident;
 use regex::Regex;
+use serde::Serialize;
 use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+pub mod bundled;
 
 mod utils {
     pub fn get_segments(p: &str) -> Vec<&str> {
         let stripped = p.strip_prefix('/').unwrap_or(p);
         stripped.split('/').collect::<Vec<&str>>()
     }
+
+    pub fn is_param(segment: &str) -> bool {
+        segment.starts_with('[') && segment.ends_with(']')
+    }
+
+    /// Whether `segment` is a `(group)` directory, which is not part of the URL.
+    pub fn is_group(segment: &str) -> bool {
+        segment.starts_with('(') && segment.ends_with(')')
+    }
+
+    /// Name of the parameter in a `[name]`, `[name=constraint]`, `[...name]` or `[[...name]]`
+    /// segment.
+    pub fn param_name(segment: &str) -> &str {
+        let name = segment
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .trim_start_matches("...");
+        name.split_once('=').map_or(name, |(name, _)| name)
+    }
+
+    /// Constraint of a `[name=constraint]` segment. Catch-alls are not constrained.
+    pub fn param_constraint(segment: &str) -> Option<&str> {
+        if !is_param(segment) || segment.starts_with("[...") || segment.starts_with("[[") {
+            return None;
+        }
+        segment[1..segment.len() - 1]
+            .split_once('=')
+            .map(|(_, constraint)| constraint)
+    }
 }
 
-use utils::get_segments;
+use utils::{get_segments, is_group, is_param, param_constraint, param_name};
+
+/// Whether the file at `path` is a route, as opposed to a private module that routes include
+/// with `mod` statements: `mod.rs` files, and files or directories starting with `_`, e.g.
+/// `api/_utils.rs` or `api/users/_db/pool.rs`.
+pub fn is_route_file(path: &str) -> bool {
+    let segments = get_segments(path);
+    segments.last() != Some(&"mod.rs") && !segments.iter().any(|s| s.starts_with('_'))
+}
+
+/// For the build script of a crate using `bundled_api`: reruns it, and so recompiles the crate,
+/// whenever a file in `dir` is added. `bundled_api` tracks changed and removed files itself, but
+/// cargo can't tell it about new routes.
+///
+/// `dir` is the `dir` argument of `bundled_api`, `"api"` by default.
+///
+/// ```no_run
+/// // In the `main` function of build.rs, for `#[bundled_api(dir = "src/routes")]`
+/// vercel_runtime_router::track_routes("src/routes");
+/// ```
+pub fn track_routes(dir: &str) {
+    println!("cargo:rerun-if-changed={}", dir);
+}
 
 lazy_static! {
+        // Constraints registered with `register_constraint`, by name
+        static ref CONSTRAINTS: RwLock<HashMap<String, (String, Regex)>> = RwLock::new(HashMap::new());
+        static ref UUID_REGEX: Regex = Regex::new(&format!("^{}$", UUID_PATTERN)).unwrap();
         // Dynamic Route - /api/[id]
         static ref DYNAMIC_ROUTE_REGEX: Regex = Regex::new(r"\[[^/\.]+\]").unwrap();
         // Catch-all Route - /api/[...slug]
@@ -22,6 +83,180 @@ lazy_static! {
         static ref DYNAMIC_OPTIONAL_CATCH_ALL_REGEX: Regex = Regex::new(r"\[{2}\.{3}\S+\]{2}").unwrap();
 }
 
+const UUID_PATTERN: &str =
+    "[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}";
+
+const BUILTIN_CONSTRAINTS: &[&str] = &["u32", "u64", "i32", "i64", "uuid"];
+
+/// Registers the constraint `name`, so that `[param=name]` segments only match values matching
+/// `pattern` entirely, in addition to the built-in `u32`, `u64`, `i32`, `i64` and `uuid`.
+///
+/// Parameters with an unregistered constraint never match. Built-in constraints cannot be
+/// replaced.
+pub fn register_constraint(name: &str, pattern: &str) -> Result<(), ConstraintError> {
+    if BUILTIN_CONSTRAINTS.contains(&name) {
+        return Err(ConstraintError::Builtin(name.to_string()));
+    }
+    let regex = Regex::new(&format!("^(?:{})$", pattern)).map_err(ConstraintError::Regex)?;
+    CONSTRAINTS
+        .write()
+        .unwrap()
+        .insert(name.to_string(), (pattern.to_string(), regex));
+    Ok(())
+}
+
+/// Whether the segment `value` satisfies `constraint`.
+fn satisfies(constraint: &str, value: &str) -> bool {
+    let digits = |v: &str| !v.is_empty() && v.bytes().all(|b| b.is_ascii_digit());
+    let signed = |v: &str| digits(v.strip_prefix('-').unwrap_or(v));
+
+    match constraint {
+        "u32" => digits(value) && value.parse::<u32>().is_ok(),
+        "u64" => digits(value) && value.parse::<u64>().is_ok(),
+        "i32" => signed(value) && value.parse::<i32>().is_ok(),
+        "i64" => signed(value) && value.parse::<i64>().is_ok(),
+        "uuid" => UUID_REGEX.is_match(value),
+        name => CONSTRAINTS
+            .read()
+            .unwrap()
+            .get(name)
+            .is_some_and(|(_, regex)| regex.is_match(value)),
+    }
+}
+
+/// Regex matching the values satisfying `constraint`, or any segment without one.
+///
+/// Constraints added with [`register_constraint`] are usually only registered in the function
+/// itself, not in the process generating routes, so unknown ones match any segment and are
+/// checked by the bundled handler's [`Router`].
+fn constraint_pattern(constraint: Option<&str>) -> String {
+    match constraint {
+        Some("u32") => at_most(&u32::MAX.to_string()),
+        Some("u64") => at_most(&u64::MAX.to_string()),
+        Some("i32") => format!(
+            "(?:-{}|{})",
+            at_most(&i32::MIN.unsigned_abs().to_string()),
+            at_most(&i32::MAX.to_string())
+        ),
+        Some("i64") => format!(
+            "(?:-{}|{})",
+            at_most(&i64::MIN.unsigned_abs().to_string()),
+            at_most(&i64::MAX.to_string())
+        ),
+        Some("uuid") => UUID_PATTERN.to_string(),
+        Some(name) => match CONSTRAINTS.read().unwrap().get(name) {
+            Some((pattern, _)) => format!("(?:{})", pattern),
+            None => "[^/]+".to_string(),
+        },
+        None => "[^/]+".to_string(),
+    }
+}
+
+/// Regex matching the decimal numbers up to `max`, with leading zeros as `str::parse` accepts
+/// them, e.g. `0*(?:[0-9]{1,1}|[0-1][0-9]{1}|2[0-4]|25)` for `25`.
+fn at_most(max: &str) -> String {
+    let mut alternatives = vec![];
+    if max.len() > 1 {
+        alternatives.push(format!("[0-9]{{1,{}}}", max.len() - 1));
+    }
+    for (i, digit) in max.bytes().enumerate().filter(|(_, d)| *d > b'0') {
+        let rest = match max.len() - i - 1 {
+            0 => String::new(),
+            n => format!("[0-9]{{{}}}", n),
+        };
+        alternatives.push(format!("{}[0-{}]{}", &max[..i], (digit - 1) as char, rest));
+    }
+    alternatives.push(max.to_string());
+    format!("0*(?:{})", alternatives.join("|"))
+}
+
+/// Why [`register_constraint`] rejected a constraint.
+#[derive(Debug)]
+pub enum ConstraintError {
+    /// The name is one of the built-in constraints
+    Builtin(String),
+    /// The pattern is not a valid regex
+    Regex(regex::Error),
+}
+
+impl std::fmt::Display for ConstraintError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConstraintError::Builtin(name) => {
+                write!(f, "the built-in constraint `{}` cannot be replaced", name)
+            }
+            ConstraintError::Regex(e) => write!(f, "invalid constraint pattern: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConstraintError {}
+
+/// Characters escaped in a parameter's path segment: those of the URL standard's path percent-encode
+/// set, `/` and `%`.
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}')
+    .add(b'/')
+    .add(b'%');
+
+/// Why [`Route::url`] could not build a URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrlError {
+    /// No route is built from the file
+    UnknownRoute(String),
+    /// The route's parameter is not supplied
+    MissingParam(String),
+    /// A supplied parameter is not part of the route
+    UnexpectedParam(String),
+    /// The value is empty or does not satisfy the parameter's constraint
+    InvalidParam { name: String, value: String },
+}
+
+impl std::fmt::Display for UrlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UrlError::UnknownRoute(file) => write!(f, "no route is built from {}", file),
+            UrlError::MissingParam(name) => write!(f, "missing parameter `{}`", name),
+            UrlError::UnexpectedParam(name) => write!(f, "unexpected parameter `{}`", name),
+            UrlError::InvalidParam { name, value } => {
+                write!(f, "invalid value {:?} for parameter `{}`", value, name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UrlError {}
+
+/// Parameters of a request path matched by a dynamic route, in the order of their segments.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Params(Vec<(String, String)>);
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Parses the parameter `name`, e.g. the value of an `[id=u64]` segment as a `u64`.
+    pub fn parse<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.get(name)?.parse().ok()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+}
+
 #[derive(Debug, PartialEq, PartialOrd)]
 pub enum RouteKind {
     Static,
@@ -66,7 +301,10 @@ impl Ord for Route {
                                     .rev()
                                     .position(|os| os.starts_with('[') && os.ends_with(']'));
 
-                                return o_pos.cmp(&s_pos);
+                                // Constrained parameters are tried before unconstrained ones
+                                return o_pos
+                                    .cmp(&s_pos)
+                                    .then(other.constraints().cmp(&self.constraints()));
                             }
 
                             o.len().cmp(&s.len())
@@ -111,16 +349,33 @@ impl From<&str> for Route {
     fn from(file_path: &str) -> Self {
         let file_path = file_path.to_string();
         let route = file_path.strip_suffix(".rs").unwrap_or(&file_path);
+        // `index.rs` serves its directory, e.g. `api/users/index.rs` serves `api/users`, and the
+        // root when routes are not served below a base path
+        let route = match route {
+            "index" => "",
+            route => route.strip_suffix("/index").unwrap_or(route),
+        };
+        // `(group)` directories organise routes without changing their URL, e.g.
+        // `api/(billing)/invoices.rs` serves `api/invoices`
+        let route = match route.split('/').any(is_group) {
+            true => route
+                .split('/')
+                .filter(|s| !is_group(s))
+                .collect::<Vec<_>>()
+                .join("/"),
+            false => route.to_owned(),
+        };
+        let route = route.as_str();
 
+        // Every character which can't be part of an identifier becomes `_`, e.g. in
+        // `api/v1.0/users/[...slug].rs`
         let module_name = file_path.strip_prefix('/').unwrap_or(&file_path);
-        let module_name = module_name.replace('/', "_");
-
-        let module_name = module_name.replace('[', "_");
-        let module_name = module_name.replace(']', "_");
-        let module_name = module_name.replace("...", "___");
-
-        let module_name = module_name.replace('-', "_");
-        let module_name = module_name.strip_suffix(".rs").unwrap_or(&module_name);
+        let module_name = module_name.strip_suffix(".rs").unwrap_or(module_name);
+        let module_name = module_name.replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+        let module_name = match module_name.starts_with(|c: char| c.is_ascii_digit()) {
+            true => format!("_{}", module_name),
+            false => module_name,
+        };
 
         // TODO validation that [...slug] and [[...slug]] can only be in the last segment
         let get_route_kind = |r: &str| -> RouteKind {
@@ -167,6 +422,199 @@ impl From<&str> for Route {
     }
 }
 
+impl Route {
+    /// Whether `req_path` (without a leading slash, e.g. `api/users/42`) is served by this route.
+    /// Dynamic segments match any non-empty segment satisfying their constraint, catch-alls one
+    /// or more segments.
+    pub fn matches(&self, req_path: &str) -> bool {
+        let Some(ref segments) = self.segments else {
+            return self.path == req_path;
+        };
+        let path = get_segments(req_path);
+
+        match self.kind {
+            RouteKind::Static => self.path == req_path,
+            RouteKind::Dynamic => segments.len() == path.len() && segments_match(segments, &path),
+            RouteKind::CatchAll | RouteKind::OptionalCatchAll => {
+                let prefix = &segments[..segments.len() - 1];
+                path.len() > prefix.len()
+                    && segments_match(prefix, &path[..prefix.len()])
+                    && !path[prefix.len()..].join("/").is_empty()
+            }
+        }
+    }
+
+    /// Whether `req_path` is the path of an optional catch-all route without any of the
+    /// segments it catches, e.g. `api/optional` for `api/optional/[[...slug]]`.
+    fn matches_optional_root(&self, req_path: &str) -> bool {
+        match (&self.kind, &self.segments) {
+            (RouteKind::OptionalCatchAll, Some(segments)) => {
+                let prefix = &segments[..segments.len() - 1];
+                let path = get_segments(req_path);
+                path.len() == prefix.len() && segments_match(prefix, &path)
+            }
+            _ => false,
+        }
+    }
+
+    /// Regex in the syntax of Vercel's `routes[].src` matching the same paths as
+    /// [`Route::matches`], with a named capture for every parameter. Constraints not
+    /// registered in this process match any segment.
+    pub fn src(&self) -> String {
+        let mut src = String::from("^");
+        for segment in self.path.split('/') {
+            let name = param_name(segment);
+            match segment {
+                s if s.starts_with("[[...") => src.push_str(&format!("(?:/(?<{}>.+))?", name)),
+                s if s.starts_with("[...") => src.push_str(&format!("/(?<{}>.+)", name)),
+                s if is_param(s) => {
+                    let pattern = constraint_pattern(param_constraint(s));
+                    src.push_str(&format!("/(?<{}>{})", name, pattern));
+                }
+                s => {
+                    src.push('/');
+                    src.push_str(&regex::escape(s));
+                }
+            }
+        }
+        src.push('$');
+        src
+    }
+
+    /// The route's path, passing the parameters captured by [`Route::src`] as query parameters.
+    pub fn dest(&self) -> String {
+        let query = self
+            .path
+            .split('/')
+            .filter(|s| is_param(s))
+            .map(|s| format!("{0}=${0}", param_name(s)))
+            .collect::<Vec<_>>();
+
+        match query.is_empty() {
+            true => format!("/{}", self.path),
+            false => format!("/{}?{}", self.path, query.join("&")),
+        }
+    }
+
+    /// The parameters of `req_path` if the route serves it, e.g. `id` = `42` for `api/users/42`
+    /// and `api/users/[id]`. Catch-alls hold the rest of the path, e.g. `a/b`. Values are
+    /// percent-decoded.
+    pub fn params(&self, req_path: &str) -> Option<Params> {
+        if !self.matches(req_path) && !self.matches_optional_root(req_path) {
+            return None;
+        }
+
+        let path = get_segments(req_path);
+        let mut params = vec![];
+        for (i, segment) in self.path.split('/').enumerate() {
+            if !is_param(segment) {
+                continue;
+            }
+            let name = param_name(segment).to_string();
+            if segment.starts_with("[...") || segment.starts_with("[[...") {
+                if path.len() > i {
+                    let rest = path[i..].iter().map(|s| decode(s)).collect::<Vec<_>>();
+                    params.push((name, rest.join("/")));
+                }
+                break;
+            }
+            params.push((name, decode(path[i])));
+        }
+        Some(Params(params))
+    }
+
+    /// The URL of the route, e.g. `/api/users/42` for `api/users/[id=u64]` and `id` = `42`.
+    ///
+    /// Values are percent-encoded, apart from the `/` separating the segments of a catch-all.
+    /// Every parameter must be supplied, except an optional catch-all, and satisfy its constraint.
+    pub fn url(&self, params: &[(&str, &str)]) -> Result<String, UrlError> {
+        if let Some((name, _)) = params.iter().find(|(name, _)| {
+            !self
+                .path
+                .split('/')
+                .any(|s| is_param(s) && param_name(s) == *name)
+        }) {
+            return Err(UrlError::UnexpectedParam(name.to_string()));
+        }
+
+        let mut url = String::new();
+        for segment in self.path.split('/') {
+            if !is_param(segment) {
+                url.push('/');
+                url.push_str(segment);
+                continue;
+            }
+
+            let name = param_name(segment);
+            let value = params.iter().find(|(n, _)| *n == name).map(|(_, v)| *v);
+            let invalid = || UrlError::InvalidParam {
+                name: name.to_string(),
+                value: value.unwrap_or_default().to_string(),
+            };
+            match value {
+                None | Some("") if segment.starts_with("[[...") => {}
+                None => return Err(UrlError::MissingParam(name.to_string())),
+                Some(value) if segment.starts_with("[...") || segment.starts_with("[[...") => {
+                    for part in value.split('/') {
+                        if part.is_empty() {
+                            return Err(invalid());
+                        }
+                        url.push('/');
+                        url.extend(utf8_percent_encode(part, SEGMENT));
+                    }
+                }
+                Some(value) => {
+                    if value.is_empty()
+                        || param_constraint(segment).is_some_and(|c| !satisfies(c, value))
+                    {
+                        return Err(invalid());
+                    }
+                    url.push('/');
+                    url.extend(utf8_percent_encode(value, SEGMENT));
+                }
+            }
+        }
+        Ok(url)
+    }
+
+    /// Number of constrained parameters.
+    fn constraints(&self) -> usize {
+        self.path.split('/').filter_map(param_constraint).count()
+    }
+
+    /// The route's path without parameter names, equal for routes matching the same paths.
+    fn shape(&self) -> String {
+        self.path
+            .split('/')
+            .map(|segment| match segment {
+                s if s.starts_with("[[...") => "[[...]]".to_string(),
+                s if s.starts_with("[...") => "[...]".to_string(),
+                s if is_param(s) => format!("[={}]", param_constraint(s).unwrap_or_default()),
+                s => s.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+fn decode(segment: &str) -> String {
+    percent_decode_str(segment).decode_utf8_lossy().into_owned()
+}
+
+fn segments_match(route: &[String], path: &[&str]) -> bool {
+    route.iter().zip(path).all(|(r, p)| match is_param(r) {
+        true => !p.is_empty() && param_constraint(r).is_none_or(|c| satisfies(c, p)),
+        false => r == p,
+    })
+}
+
+/// An entry of the `routes` of a Build Output API `config.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VercelRoute {
+    pub src: String,
+    pub dest: String,
+}
+
 pub struct Router {
     pub routes: Vec<Route>,
 }
@@ -179,7 +627,11 @@ impl Default for Router {
 
 impl From<Vec<&str>> for Router {
     fn from(raw_paths: Vec<&str>) -> Self {
-        let mut routes: Vec<Route> = raw_paths.into_iter().map(Route::from).collect();
+        let mut routes: Vec<Route> = raw_paths
+            .into_iter()
+            .filter(|p| is_route_file(p))
+            .map(Route::from)
+            .collect();
         routes.sort();
         Router { routes }
     }
@@ -190,9 +642,9 @@ impl Router {
         let mut routes = glob(file_pattern)
             .expect("Failed to read glob pattern")
             .filter_map(|e| e.ok())
-            .map(|raw_path| {
+            .filter_map(|raw_path| {
                 let path = raw_path.to_str().unwrap();
-                Route::from(path)
+                is_route_file(path).then(|| Route::from(path))
             })
             .collect::<Vec<_>>();
 
@@ -201,53 +653,71 @@ impl Router {
     }
 
     pub fn call(&self, req_path: &str) -> Option<&Route> {
-        // Check if there is an optional catch all route
-        if let Some(optional_catch_all) = self.routes.iter().find(|r| {
-            let dynamic_optional_catch_all_exp = Regex::new(r"\[{2}\.{3}\S+\]{2}").unwrap();
-            let optional_catchall_route =
-                dynamic_optional_catch_all_exp.replace_all(r.path.as_str(), "");
-            let optional_catchall_route = optional_catchall_route.trim_end_matches('/');
-
-            r.kind == RouteKind::OptionalCatchAll && req_path == optional_catchall_route
-        }) {
+        // Optional catch-alls take precedence for the path they are mounted at
+        if let Some(optional_catch_all) = self
+            .routes
+            .iter()
+            .find(|r| r.matches_optional_root(req_path))
+        {
             return Some(optional_catch_all);
         };
 
-        let result = self.routes.iter().find(|route| {
-            match route.kind {
-                RouteKind::Static => route.path == req_path,
-                RouteKind::Dynamic => {
-                    let path_segements = get_segments(req_path);
-                    // Check if all segements are identical (ignoring wildcards)
-                    match route.segments {
-                        None => false,
-                        Some(ref route_segments) => {
-                            if route_segments.len() != path_segements.len() {
-                                return false;
-                            }
+        self.routes.iter().find(|route| route.matches(req_path))
+    }
 
-                            route_segments.iter().enumerate().all(|(i, rs)| {
-                                (rs.contains('[') && rs.contains(']')) || rs == path_segements[i]
-                            })
-                        }
-                    }
-                }
-                RouteKind::OptionalCatchAll => {
-                    // todo extract logic
-                    let optional_catchall_prefix =
-                        DYNAMIC_OPTIONAL_CATCH_ALL_REGEX.replace_all(route.path.as_str(), "");
-                    req_path.starts_with(optional_catchall_prefix.as_ref())
-                }
-                RouteKind::CatchAll => {
-                    // todo extract logic
-                    let catchall_prefix =
-                        DYNAMIC_CATCH_ALL_REGEX.replace_all(route.path.as_str(), "");
-                    req_path.starts_with(catchall_prefix.as_ref())
-                }
+    /// Pairs of routes matching the same paths, e.g. `api/users.rs` and `api/users/index.rs`,
+    /// or `api/(billing)/[id].rs` and `api/(shop)/[slug].rs`, of which [`Router::call`] only
+    /// ever returns the first.
+    pub fn conflicts(&self) -> Vec<(&Route, &Route)> {
+        let mut conflicts = vec![];
+        for (i, route) in self.routes.iter().enumerate() {
+            let shape = route.shape();
+            if let Some(first) = self.routes[..i].iter().find(|r| r.shape() == shape) {
+                conflicts.push((first, route));
             }
-        });
+        }
+        conflicts
+    }
 
-        result
+    /// The URL of the route built from `module_file`, e.g. `api/users/[id].rs`, see
+    /// [`Route::url`].
+    pub fn url(&self, module_file: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+        self.routes
+            .iter()
+            .find(|r| r.module_file == module_file)
+            .ok_or_else(|| UrlError::UnknownRoute(module_file.to_string()))?
+            .url(params)
+    }
+
+    /// Routes matching request paths in the same order as [`Router::call`], for the `routes`
+    /// of a [Build Output API](https://vercel.com/docs/build-output-api/v3) `config.json`.
+    pub fn to_vercel_routes(&self) -> Vec<VercelRoute> {
+        let optional_roots = self
+            .routes
+            .iter()
+            .filter(|r| r.kind == RouteKind::OptionalCatchAll)
+            .map(|r| {
+                let root = r.path.rsplit_once('/').map_or("", |(root, _)| root);
+                VercelRoute {
+                    src: Route::from(root).src(),
+                    dest: format!("/{}", r.path),
+                }
+            });
+
+        optional_roots
+            .chain(self.routes.iter().map(|r| VercelRoute {
+                src: r.src(),
+                dest: r.dest(),
+            }))
+            .collect()
+    }
+
+    /// A Build Output API v3 `config.json` holding the routes of [`Router::to_vercel_routes`].
+    pub fn build_output_config(&self) -> serde_json::Value {
+        serde_json::json!({
+            "version": 3,
+            "routes": self.to_vercel_routes(),
+        })
     }
 }
 
@@ -312,6 +782,318 @@ mod tests {
         insta::assert_debug_snapshot!(router.call("api/github/ecklf/rust-at-home/tags/v0.1.0"));
         insta::assert_debug_snapshot!(router.call("api/github/ecklf/rust-at-home/tags/latest"));
     }
+
+    #[test]
+    fn index_routing() {
+        let router = Router::from(vec![
+            "api/index.rs",
+            "api/users/index.rs",
+            "api/users/[id].rs",
+            "api/[org]/index.rs",
+            "api/[org]/members.rs",
+        ]);
+        let call = |path| router.call(path).map(|r| r.module_file.as_str());
+
+        assert_eq!(call("api"), Some("api/index.rs"));
+        assert_eq!(call("api/users"), Some("api/users/index.rs"));
+        assert_eq!(call("api/users/42"), Some("api/users/[id].rs"));
+        assert_eq!(call("api/acme"), Some("api/[org]/index.rs"));
+        assert_eq!(call("api/acme/members"), Some("api/[org]/members.rs"));
+        assert!(router.conflicts().is_empty());
+
+        let root = Router::from(vec!["index.rs", "users.rs"]);
+        assert_eq!(
+            root.call("").map(|r| r.module_file.as_str()),
+            Some("index.rs")
+        );
+        assert_eq!(root.routes[1].src(), "^/$");
+
+        let router = Router::from(vec![
+            "api/posts.rs",
+            "api/users.rs",
+            "api/users/index.rs",
+            "api/[org].rs",
+            "api/[org]/index.rs",
+        ]);
+        let conflicts = router
+            .conflicts()
+            .into_iter()
+            .map(|(a, b)| (a.module_file.as_str(), b.module_file.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            conflicts,
+            [
+                ("api/users.rs", "api/users/index.rs"),
+                ("api/[org].rs", "api/[org]/index.rs"),
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod group_tests {
+    use super::Router;
+
+    #[test]
+    fn it_routes_groups_by_their_url() {
+        let router = Router::from(vec![
+            "api/(billing)/invoices.rs",
+            "api/(billing)/invoices/[id].rs",
+            "api/(shop)/products/[slug].rs",
+        ]);
+        let call = |path| router.call(path).map(|r| r.module_file.as_str());
+
+        assert_eq!(call("api/invoices"), Some("api/(billing)/invoices.rs"));
+        assert_eq!(
+            call("api/invoices/42"),
+            Some("api/(billing)/invoices/[id].rs")
+        );
+        assert_eq!(
+            call("api/products/hat"),
+            Some("api/(shop)/products/[slug].rs")
+        );
+        assert_eq!(call("api/(billing)/invoices"), None);
+        assert!(router.conflicts().is_empty());
+    }
+
+    #[test]
+    fn it_detects_collisions_between_groups() {
+        let router = Router::from(vec![
+            "api/(billing)/items.rs",
+            "api/(shop)/items.rs",
+            "api/(billing)/[id].rs",
+            "api/(shop)/[slug].rs",
+            "api/(shop)/[...rest].rs",
+        ]);
+        let conflicts = router
+            .conflicts()
+            .into_iter()
+            .map(|(a, b)| (a.module_file.as_str(), b.module_file.as_str()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            conflicts,
+            [
+                ("api/(billing)/items.rs", "api/(shop)/items.rs"),
+                ("api/(billing)/[id].rs", "api/(shop)/[slug].rs"),
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod constraint_tests {
+    use super::{
+        constraint_pattern, register_constraint, ConstraintError, Route, Router,
+        BUILTIN_CONSTRAINTS,
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn it_enforces_built_in_constraints() {
+        let router = Router::from(vec![
+            "api/users/me.rs",
+            "api/users/[name].rs",
+            "api/users/[id=u64].rs",
+            "api/offsets/[offset=i32].rs",
+            "api/sessions/[id=uuid].rs",
+        ]);
+        let call = |path| router.call(path).map(|r| r.module_file.as_str());
+
+        assert_eq!(call("api/users/me"), Some("api/users/me.rs"));
+        assert_eq!(call("api/users/42"), Some("api/users/[id=u64].rs"));
+        assert_eq!(call("api/users/ecklf"), Some("api/users/[name].rs"));
+        assert_eq!(call("api/users/-1"), Some("api/users/[name].rs"));
+        assert_eq!(call("api/users/+1"), Some("api/users/[name].rs"));
+        assert_eq!(
+            call("api/users/99999999999999999999"),
+            Some("api/users/[name].rs")
+        );
+        assert_eq!(call("api/offsets/-5"), Some("api/offsets/[offset=i32].rs"));
+        assert_eq!(call("api/offsets/5000000000"), None);
+        assert_eq!(
+            call("api/sessions/67e55044-10b1-426f-9247-bb680e5fe0c8"),
+            Some("api/sessions/[id=uuid].rs")
+        );
+        assert_eq!(call("api/sessions/67e55044"), None);
+        assert!(router.conflicts().is_empty());
+    }
+
+    #[test]
+    fn it_enforces_registered_constraints() {
+        let route = Route::from("api/posts/[slug=test_slug].rs");
+        assert!(!route.matches("api/posts/hello-world"));
+
+        register_constraint("test_slug", "[a-z0-9]+(-[a-z0-9]+)*").unwrap();
+        assert!(route.matches("api/posts/hello-world"));
+        assert!(!route.matches("api/posts/Hello"));
+        assert!(!route.matches("api/posts/hello-"));
+        assert_eq!(
+            route.src(),
+            "^/api/posts/(?<slug>(?:[a-z0-9]+(-[a-z0-9]+)*))$"
+        );
+
+        assert!(register_constraint("broken", "(").is_err());
+    }
+
+    #[test]
+    fn it_generates_the_constraint_patterns_of_the_node_builder() {
+        // Shared with the tests of `src/lib/routes.ts`
+        let patterns: HashMap<String, String> =
+            serde_json::from_str(include_str!("../../../test/constraint-patterns.json")).unwrap();
+
+        assert_eq!(patterns.len(), BUILTIN_CONSTRAINTS.len());
+        for (name, pattern) in patterns {
+            assert_eq!(constraint_pattern(Some(&name)), pattern, "{}", name);
+        }
+    }
+
+    #[test]
+    fn it_rejects_replacing_built_in_constraints() {
+        assert!(matches!(
+            register_constraint("u64", "[a-z]+"),
+            Err(ConstraintError::Builtin(name)) if name == "u64"
+        ));
+        assert!(Route::from("api/users/[id=u64].rs").matches("api/users/42"));
+        assert!(!Route::from("api/users/[id=u64].rs").matches("api/users/abc"));
+    }
+
+    #[test]
+    fn it_extracts_typed_params() {
+        let route = Route::from("api/orgs/[org]/repos/[id=u64]/[...path].rs");
+        assert_eq!(route.path, "api/orgs/[org]/repos/[id=u64]/[...path]");
+        assert_eq!(route.module_name, "api_orgs__org__repos__id_u64______path_");
+        assert_eq!(
+            route.dest(),
+            "/api/orgs/[org]/repos/[id=u64]/[...path]?org=$org&id=$id&path=$path"
+        );
+
+        let params = route.params("api/orgs/vercel/repos/42/src/lib.rs").unwrap();
+        assert_eq!(params.get("org"), Some("vercel"));
+        assert_eq!(params.parse::<u64>("id"), Some(42));
+        assert_eq!(params.get("path"), Some("src/lib.rs"));
+        assert_eq!(params.iter().count(), 3);
+        assert!(route.params("api/orgs/vercel/repos/latest/src").is_none());
+
+        let optional = Route::from("api/docs/[[...slug]].rs");
+        assert_eq!(optional.params("api/docs").unwrap().get("slug"), None);
+        assert_eq!(
+            optional.params("api/docs/a/b").unwrap().get("slug"),
+            Some("a/b")
+        );
+    }
+}
+
+#[cfg(test)]
+mod url_tests {
+    use super::{Route, Router, UrlError};
+
+    #[test]
+    fn it_builds_urls_from_params() {
+        let router = Router::from(vec![
+            "api/index.rs",
+            "api/(team)/members.rs",
+            "api/github/[owner]/[repo]/releases/[tag].rs",
+            "api/users/[id=u64].rs",
+        ]);
+
+        assert_eq!(router.url("api/index.rs", &[]), Ok("/api".to_string()));
+        assert_eq!(
+            router.url("api/(team)/members.rs", &[]),
+            Ok("/api/members".to_string())
+        );
+        assert_eq!(
+            router.url(
+                "api/github/[owner]/[repo]/releases/[tag].rs",
+                &[
+                    ("owner", "vercel"),
+                    ("repo", "rust"),
+                    ("tag", "v1.0 beta/2")
+                ]
+            ),
+            Ok("/api/github/vercel/rust/releases/v1.0%20beta%2F2".to_string())
+        );
+        assert_eq!(
+            router.url("api/users/[id=u64].rs", &[("id", "42")]),
+            Ok("/api/users/42".to_string())
+        );
+
+        assert_eq!(
+            router.url("api/users/[id].rs", &[("id", "42")]),
+            Err(UrlError::UnknownRoute("api/users/[id].rs".to_string()))
+        );
+        assert_eq!(
+            router.url("api/users/[id=u64].rs", &[]),
+            Err(UrlError::MissingParam("id".to_string()))
+        );
+        assert_eq!(
+            router.url("api/users/[id=u64].rs", &[("id", "42"), ("name", "x")]),
+            Err(UrlError::UnexpectedParam("name".to_string()))
+        );
+        assert_eq!(
+            router.url("api/users/[id=u64].rs", &[("id", "me")]),
+            Err(UrlError::InvalidParam {
+                name: "id".to_string(),
+                value: "me".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn it_builds_catch_all_urls() {
+        let files = Route::from("api/files/[...path].rs");
+        assert_eq!(
+            files.url(&[("path", "docs/read me.md")]),
+            Ok("/api/files/docs/read%20me.md".to_string())
+        );
+        assert!(files.url(&[("path", "")]).is_err());
+        assert!(files.url(&[("path", "docs//a")]).is_err());
+
+        let docs = Route::from("api/docs/[[...slug]].rs");
+        assert_eq!(docs.url(&[]), Ok("/api/docs".to_string()));
+        assert_eq!(docs.url(&[("slug", "")]), Ok("/api/docs".to_string()));
+        assert_eq!(
+            docs.url(&[("slug", "a/b")]),
+            Ok("/api/docs/a/b".to_string())
+        );
+    }
+
+    #[test]
+    fn it_round_trips_params() {
+        let router = Router::from(vec!["api/[org]/[...path].rs"]);
+        let params = [("org", "ACME & co"), ("path", "a b/100%/ü")];
+
+        let url = router.url("api/[org]/[...path].rs", &params).unwrap();
+        assert_eq!(url, "/api/ACME%20&%20co/a%20b/100%25/%C3%BC");
+        let req_path = url.strip_prefix('/').unwrap();
+        let route = router.call(req_path).unwrap();
+        let decoded = route.params(req_path).unwrap();
+        assert_eq!(decoded.iter().collect::<Vec<_>>(), params);
+    }
+}
+
+#[cfg(test)]
+mod private_module_tests {
+    use super::{is_route_file, Router};
+
+    #[test]
+    fn it_skips_private_modules() {
+        assert!(is_route_file("api/users/[id].rs"));
+        assert!(is_route_file("api/users/index.rs"));
+        assert!(!is_route_file("api/_utils.rs"));
+        assert!(!is_route_file("api/users/_db/pool.rs"));
+        assert!(!is_route_file("api/users/mod.rs"));
+
+        let router = Router::from(vec![
+            "api/users/index.rs",
+            "api/users/_validation.rs",
+            "api/users/mod.rs",
+            "api/_db/[id].rs",
+        ]);
+        assert_eq!(router.routes.len(), 1);
+        assert!(router.call("api/users/_validation").is_none());
+        assert!(router.call("api/_db/42").is_none());
+    }
 }
 
 #[cfg(test)]
@@ -350,6 +1132,42 @@ mod route_tests {
         );
     }
 
+    #[test]
+    fn it_maps_index_files_to_their_directory() {
+        let route = Route::from("api/users/index.rs");
+        assert!(matches!(route.kind, RouteKind::Static));
+        assert_eq!(route.path, "api/users");
+        assert_eq!(route.module_name, "api_users_index");
+
+        let route = Route::from("api/[org]/index.rs");
+        assert!(matches!(route.kind, RouteKind::Dynamic));
+        assert_eq!(route.path, "api/[org]");
+        assert_eq!(route.segments.unwrap(), vec!["api", "[org]"]);
+
+        assert_eq!(Route::from("api/index.rs").path, "api");
+        assert_eq!(Route::from("api/reindex.rs").path, "api/reindex");
+    }
+
+    #[test]
+    fn it_strips_groups_from_the_path() {
+        let route = Route::from("api/(billing)/invoices/[id].rs");
+        assert!(matches!(route.kind, RouteKind::Dynamic));
+        assert_eq!(route.path, "api/invoices/[id]");
+        assert_eq!(route.module_file, "api/(billing)/invoices/[id].rs");
+        assert_eq!(route.module_name, "api__billing__invoices__id_");
+        assert_eq!(route.segments.unwrap(), vec!["api", "invoices", "[id]"]);
+
+        assert_eq!(Route::from("api/(v1)/(admin)/index.rs").path, "api");
+    }
+
+    #[test]
+    fn it_names_modules_of_any_file() {
+        assert_eq!(Route::from("v1.0/users.rs").module_name, "v1_0_users");
+        assert_eq!(Route::from("api/my route.rs").module_name, "api_my_route");
+        assert_eq!(Route::from("2024/posts.rs").module_name, "_2024_posts");
+        assert_eq!(Route::from("api/café.rs").module_name, "api_caf_");
+    }
+
     #[test]
     fn it_creates_catch_all_route() {
         let path = "api/[...all]";
@@ -396,3 +1214,193 @@ mod route_tests {
         );
     }
 }
+
+#[cfg(test)]
+mod vercel_routes_tests {
+    use super::{Route, Router, VercelRoute};
+    use regex::Regex;
+
+    const ROUTES: &[&str] = &[
+        "api/posts.rs",
+        "api/[id].rs",
+        "api/posts/[id].rs",
+        "api/[...id].rs",
+        "api/nested/posts.rs",
+        "api/nested/[id].rs",
+        "api/nested/posts/[id].rs",
+        "api/nested/[...id].rs",
+        "api/optional/posts.rs",
+        "api/optional/[id].rs",
+        "api/optional/posts/[id].rs",
+        "api/optional.rs",
+        "api/optional/[[...id]].rs",
+        "api/deep/nested/[id]/comments/[cid].rs",
+        "api/github/[owner]/[name]/releases/[release].rs",
+        "api/github/[owner]/[name]/releases/latest.rs",
+        "api/github/[owner]/[name]/tags/[...all].rs",
+        "api/github/[owner]/[name]/tags/latest.rs",
+        "api/users/[id]/[[...rest]].rs",
+        "api/hello-world.rs",
+        "api/nested/index.rs",
+        "api/(team)/x/latest.rs",
+        "api/tags/[n=u32].rs",
+        "api/tags/[n=u32]/x.rs",
+        "api/tags/[tag]/x.rs",
+        "api/ids/[id=i32].rs",
+        "api/ids/[id=i64]/x.rs",
+        "api/ids/[id=u64]/y.rs",
+        "api/ids/[id]/[...rest].rs",
+    ];
+
+    #[test]
+    fn it_generates_src_and_dest() {
+        let route = |path| {
+            let route = Route::from(path);
+            (route.src(), route.dest())
+        };
+
+        assert_eq!(
+            route("api/hello-world.rs"),
+            (
+                r"^/api/hello\-world$".to_string(),
+                "/api/hello-world".to_string()
+            )
+        );
+        assert_eq!(
+            route("api/post/[id]/comments/[commentId].rs"),
+            (
+                "^/api/post/(?<id>[^/]+)/comments/(?<commentId>[^/]+)$".to_string(),
+                "/api/post/[id]/comments/[commentId]?id=$id&commentId=$commentId".to_string()
+            )
+        );
+        assert_eq!(
+            route("api/[owner]/[...all].rs"),
+            (
+                "^/api/(?<owner>[^/]+)/(?<all>.+)$".to_string(),
+                "/api/[owner]/[...all]?owner=$owner&all=$all".to_string()
+            )
+        );
+        assert_eq!(
+            route("api/optional/[[...slug]].rs"),
+            (
+                "^/api/optional(?:/(?<slug>.+))?$".to_string(),
+                "/api/optional/[[...slug]]?slug=$slug".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn it_generates_a_build_output_config() {
+        let router = Router::from(vec!["api/posts.rs", "api/[[...slug]].rs"]);
+        let config = router.build_output_config();
+
+        assert_eq!(config["version"], 3);
+        assert_eq!(
+            router.to_vercel_routes(),
+            vec![
+                VercelRoute {
+                    src: "^/api$".to_string(),
+                    dest: "/api/[[...slug]]".to_string(),
+                },
+                VercelRoute {
+                    src: "^/api/posts$".to_string(),
+                    dest: "/api/posts".to_string(),
+                },
+                VercelRoute {
+                    src: "^/api(?:/(?<slug>.+))?$".to_string(),
+                    dest: "/api/[[...slug]]?slug=$slug".to_string(),
+                },
+            ]
+        );
+        assert_eq!(config["routes"][1]["src"], "^/api/posts$");
+    }
+
+    #[test]
+    fn it_leaves_unknown_constraints_to_the_handler() {
+        let router = Router::from(vec!["api/posts.rs", "api/[id=nope].rs"]);
+        assert_eq!(
+            router.to_vercel_routes()[1],
+            VercelRoute {
+                src: "^/api/(?<id>[^/]+)$".to_string(),
+                dest: "/api/[id=nope]?id=$id".to_string(),
+            }
+        );
+    }
+
+    /// Every path of up to six segments built from the segments used by `ROUTES`.
+    fn paths() -> Vec<String> {
+        let alphabet = [
+            "posts", "nested", "optional", "github", "tags", "latest", "x", "",
+        ];
+        let mut paths = vec!["api".to_string()];
+        let mut frontier = vec!["api".to_string()];
+        for _ in 0..5 {
+            frontier = frontier
+                .iter()
+                .flat_map(|p| alphabet.iter().map(move |s| format!("{}/{}", p, s)))
+                .collect();
+            paths.extend(frontier.iter().cloned());
+        }
+        paths.extend(
+            [
+                "",
+                "apix",
+                "api/hello-world",
+                "api/hello_world",
+                "api/users/42",
+                "api/users/42/a/b",
+                "api/users//a",
+                "api/deep/nested/1/comments/2",
+                "api/deep/nested//comments/2",
+                "api/github/ecklf/rust/releases/v1",
+                "api/github/ecklf/rust/tags/v0.1.0",
+                "api/tags/42",
+                "api/tags/42/x",
+                "api/tags/-42/x",
+                "api/tags/v1/x",
+                "api/tags/4294967295",
+                "api/tags/4294967296",
+                "api/tags/0004294967295/x",
+                "api/tags/99999999999999999999",
+                "api/users/99999999999999999999",
+                "api/ids/2147483647",
+                "api/ids/2147483648",
+                "api/ids/-2147483648",
+                "api/ids/-2147483649",
+                "api/ids/-0",
+                "api/ids/+1",
+                "api/ids/9223372036854775807/x",
+                "api/ids/9223372036854775808/x",
+                "api/ids/-9223372036854775808/x",
+                "api/ids/-9223372036854775809/x",
+                "api/ids/18446744073709551615/y",
+                "api/ids/18446744073709551616/y",
+                "api/ids/99999999999999999999/y",
+            ]
+            .map(String::from),
+        );
+        paths
+    }
+
+    #[test]
+    fn it_matches_the_same_routes_as_the_generated_regexes() {
+        let router = Router::from(ROUTES.to_vec());
+        let routes = router
+            .to_vercel_routes()
+            .into_iter()
+            .map(|r| (Regex::new(&r.src).unwrap(), r.dest))
+            .collect::<Vec<_>>();
+
+        let paths = paths();
+        assert!(paths.len() > 30_000);
+        for path in paths {
+            let expected = router.call(&path).map(|r| format!("/{}", r.path));
+            let generated = routes
+                .iter()
+                .find(|(src, _)| src.is_match(&format!("/{}", path)))
+                .map(|(_, dest)| dest.split('?').next().unwrap().to_string());
+
+            assert_eq!(generated, expected, "routes disagree on {:?}", path);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use adapter::axum::{
+    extract::{Path, Query},
+    response::Json,
+    routing::get,
+    Router,
+};
+use adapter::{FileRoutes, VercelApp};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use vercel_runtime::Error;
+
+// The adapter of the enabled axum version, preferring 0.8 if both are enabled
+#[cfg(all(feature = "axum07", not(feature = "axum08")))]
+use vercel_axum::axum07 as adapter;
+#[cfg(feature = "axum08")]
+use vercel_axum::axum08 as adapter;
+
+#[derive(Deserialize)]
+struct Greeting {
+    name: Option<String>,
+}
+
+async fn hello(Query(greeting): Query<Greeting>) -> Json<Value> {
+    let name = greeting.name.unwrap_or_else(|| "world".to_string());
+    Json(json!({ "message": format!("Hello, {}!", name) }))
+}
+
+async fn greet(Path(name): Path<String>) -> Json<Value> {
+    Json(json!({ "message": format!("Hello, {}!", name) }))
+}
+
+async fn version() -> Json<Value> {
+    Json(json!({ "version": env!("CARGO_PKG_VERSION") }))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    // File based paths are translated into the path syntax of the enabled axum version
+    let routes = Router::new()
+        .file_route("api/axum.rs", get(hello))
+        .file_route("api/axum/[name].rs", get(greet));
+
+    // Routes below the base path are declared without the `/api/axum` prefix
+    let meta = VercelApp::new(Router::new().route("/version", get(version)))
+        .base_path("/api/axum/meta")
+        .into_router();
+
+    adapter::run(routes.merge(meta)).await
+}
@@ -1,5 +1,6 @@
 use slack_morphism::{errors::SlackClientError, prelude::*};
-use vercel_runtime::{run, Body, Error, Request, Response, StatusCode};
+use vercel_runtime::cron::CronInvocation;
+use vercel_runtime::{Body, Error, Response, Runtime, State, StatusCode};
 
 #[derive(Debug, Clone)]
 pub struct SlackMessage {}
@@ -12,11 +13,12 @@ impl SlackMessageTemplate for SlackMessage {
     }
 }
 
-struct Lambda<'a, T: SlackClientHttpConnector + Send + Sync> {
-    slack: SlackClientSession<'a, T>,
+pub struct Slack {
+    client: SlackHyperClient,
+    token: SlackApiToken,
 }
 
-impl<T: SlackClientHttpConnector + Send + Sync> Lambda<'_, T> {
+impl Slack {
     pub async fn post_message(
         &self,
         message: &impl SlackMessageTemplate,
@@ -25,44 +27,27 @@ impl<T: SlackClientHttpConnector + Send + Sync> Lambda<'_, T> {
         let post_chat_req =
             SlackApiChatPostMessageRequest::new(channel.into(), message.render_template());
 
-        self.slack.chat_post_message(&post_chat_req).await
+        let session = self.client.open_session(&self.token);
+        session.chat_post_message(&post_chat_req).await
     }
+}
 
-    pub async fn handler(&self, req: Request) -> Result<Response<Body>, Error> {
-        let token_value = std::env::var("CRON_SECRET")?;
-        let headers = req.headers();
-
-        match headers.get("authorization") {
-            None => Ok(Response::builder()
-                .status(StatusCode::NOT_FOUND)
-                .body(().into())?),
-            Some(authorization_header) => {
-                let authorization_string = authorization_header.to_str().unwrap();
-
-                if authorization_string != format!("Bearer {}", token_value) {
-                    return Ok(Response::builder()
-                        .status(StatusCode::NOT_FOUND)
-                        .body(().into())?);
-                }
-
-                let message = SlackMessage {};
+pub async fn handler(_cron: CronInvocation, slack: State<Slack>) -> Result<Response<Body>, Error> {
+    let message = SlackMessage {};
 
-                self.post_message(&message, "#general").await?;
+    slack.post_message(&message, "#general").await?;
 
-                Ok(Response::builder().status(StatusCode::OK).body(().into())?)
-            }
-        }
-    }
+    Ok(Response::builder().status(StatusCode::OK).body(().into())?)
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    let slack_client = SlackClient::new(SlackClientHyperConnector::new());
+    let client = SlackClient::new(SlackClientHyperConnector::new());
     let token_value: SlackApiTokenValue = std::env::var("SLACK_API_TOKEN")?.into();
     let token: SlackApiToken = SlackApiToken::new(token_value);
-    let slack = slack_client.open_session(&token);
-
-    let lambda = Lambda { slack };
 
-    run(|e| lambda.handler(e)).await
+    Runtime::new()
+        .with_state(Slack { client, token })
+        .run_cron(handler)
+        .await
 }
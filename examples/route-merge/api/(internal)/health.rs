@@ -0,0 +1,8 @@
+use vercel_runtime::{Body, Error, Request, Response, StatusCode};
+
+pub async fn handler(_req: Request) -> Result<Response<Body>, Error> {
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::Text("Route is /health".into()))?)
+}
@@ -0,0 +1,11 @@
+use vercel_runtime::middleware::Next;
+use vercel_runtime::{Body, Error, Request, Response};
+
+/// Runs before the routes of the `(internal)` group only, e.g. `/api/health`.
+pub async fn middleware(req: Request, next: Next) -> Result<Response<Body>, Error> {
+    let mut response = next.run(req).await?;
+    response
+        .headers_mut()
+        .insert("cache-control", "no-store".parse()?);
+    Ok(response)
+}
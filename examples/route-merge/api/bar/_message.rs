@@ -0,0 +1,4 @@
+/// Shared by the `/bar` routes, which include it with `mod _message;`.
+pub fn message(route: &str, starter: &str) -> String {
+    format!("Route is {}, your starter is {}", route, starter)
+}
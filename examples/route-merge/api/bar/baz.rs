@@ -1,8 +1,12 @@
-use vercel_runtime::{Body, Error, Request, Response, StatusCode};
+use merged_runtime_demo::AppState;
+use vercel_runtime::{Body, Error, Request, Response, State, StatusCode};
 
-pub async fn handler(_req: Request) -> Result<Response<Body>, Error> {
+pub async fn handler(_req: Request, state: State<AppState>) -> Result<Response<Body>, Error> {
     Ok(Response::builder()
         .status(StatusCode::OK)
         .header("Content-Type", "application/json")
-        .body(Body::Text("Route is /bar/baz".into()))?)
+        .body(Body::Text(format!(
+            "Route is /bar/baz, your starter is {}",
+            state.starter
+        )))?)
 }
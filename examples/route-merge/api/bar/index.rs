@@ -0,0 +1,11 @@
+use merged_runtime_demo::AppState;
+use vercel_runtime::{Body, Error, Request, Response, State, StatusCode};
+
+mod _message;
+
+pub async fn handler(_req: Request, state: State<AppState>) -> Result<Response<Body>, Error> {
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::Text(_message::message("/bar", &state.starter)))?)
+}
@@ -0,0 +1,11 @@
+use vercel_runtime::middleware::Next;
+use vercel_runtime::{Body, Error, Request, Response};
+
+/// Runs before every route below `api/bar`.
+pub async fn middleware(req: Request, next: Next) -> Result<Response<Body>, Error> {
+    let mut response = next.run(req).await?;
+    response
+        .headers_mut()
+        .insert("x-route-group", "bar".parse()?);
+    Ok(response)
+}
@@ -1,9 +1,15 @@
-use vercel_runtime::{bundled_api, run, Body, Error, Request, Response};
+use merged_runtime_demo::{choose_starter, AppState};
+use vercel_runtime::{bundled_api, Body, Error, Request, Response, Runtime, State};
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    run(handler).await
+    Runtime::new()
+        .with_state(AppState {
+            starter: choose_starter(),
+        })
+        .run(handler)
+        .await
 }
 
-#[bundled_api(path = "examples/route-merge")]
-pub async fn handler(req: Request) -> Result<Response<Body>, Error> {}
+#[bundled_api]
+pub async fn handler(req: Request, state: State<AppState>) -> Result<Response<Body>, Error> {}
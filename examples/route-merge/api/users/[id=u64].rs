@@ -0,0 +1,30 @@
+use serde_json::json;
+use vercel_runtime::http::bad_request;
+use vercel_runtime::{Body, Error, Params, Request, Response, StatusCode};
+
+pub async fn handler(req: Request) -> Result<Response<Body>, Error> {
+    // `[id=u64]` only matches ids in range, but the handler may also be called directly
+    let Some(id) = req
+        .extensions()
+        .get::<Params>()
+        .and_then(|params| params.parse::<u64>("id"))
+    else {
+        return bad_request(json!({
+            "message": "Expected a numeric id",
+            "code": "invalid_id",
+        }));
+    };
+
+    let next = match id.checked_add(1) {
+        Some(next) => crate::routes::api_users__id_u64_(next)?,
+        None => "none".to_string(),
+    };
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(Body::Text(format!(
+            "Route is /users/[id=u64] with id {}, next is {}",
+            id, next
+        )))?)
+}
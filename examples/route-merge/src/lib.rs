@@ -1,7 +1,12 @@
 use rand::seq::SliceRandom;
 
 pub fn choose_starter() -> String {
-    let pokemons = vec!["Bulbasaur", "Charmander", "Squirtle", "Pikachu"];
+    let pokemons = ["Bulbasaur", "Charmander", "Squirtle", "Pikachu"];
     let starter = pokemons.choose(&mut rand::thread_rng()).unwrap();
     starter.to_string()
 }
+
+/// State shared by all routes of the bundled handler.
+pub struct AppState {
+    pub starter: String,
+}
@@ -2,8 +2,10 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use simple_runtime_demo::choose_starter;
 use vercel_runtime::{
-    http::bad_request, process_request, process_response, run_service, service_fn, Body, Error,
-    Request, RequestPayloadExt, Response, ServiceBuilder, StatusCode,
+    http::bad_request,
+    logging::{self, LogLayer},
+    process_request, process_response, run_service, service_fn, Body, Error, Request,
+    RequestPayloadExt, Response, ServiceBuilder, StatusCode,
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -19,16 +21,14 @@ pub struct APIError {
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::ERROR)
-        // disable printing the name of the module in every log line.
-        .with_target(false)
-        .init();
+    // Prints one JSON record per line, filtered via `RUST_LOG`
+    logging::init();
 
     // This allows to extend the tower service with more layers
     let handler = ServiceBuilder::new()
         .map_request(process_request)
         .map_response(process_response)
+        .layer(LogLayer)
         .service(service_fn(handler));
 
     run_service(handler).await
@@ -20,12 +20,10 @@ pub async fn handler(req: Request) -> Result<Response<Body>, Error> {
     let id_key = hash_query.get("id");
 
     match id_key {
-        None => {
-            return bad_request(APIError {
-                message: "Query string is invalid",
-                code: "query_string_invalid",
-            });
-        }
+        None => bad_request(APIError {
+            message: "Query string is invalid",
+            code: "query_string_invalid",
+        }),
         Some(id) => Ok(Response::builder()
             .status(StatusCode::OK)
             .header("Content-Type", "application/json")
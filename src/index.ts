@@ -26,7 +26,7 @@ import {
   gatherExtraFiles,
   runUserScripts,
 } from './lib/utils';
-import { generateRoutes, parseRoute } from './lib/routes';
+import { generateRoutes, isRouteFile, parseRoute } from './lib/routes';
 
 type RustEnv = Record<'RUSTFLAGS' | 'PATH', string>;
 
@@ -36,6 +36,11 @@ async function buildHandler(
   const BUILDER_DEBUG = Boolean(process.env.VERCEL_BUILDER_DEBUG ?? false);
   const { files, entrypoint, workPath, config, meta } = options;
 
+  if (!isRouteFile(entrypoint)) {
+    debug(`Skipping private module \`${entrypoint}\``);
+    return { output: {} };
+  }
+
   await installRustToolchain();
 
   debug('Creating file system');
@@ -1,4 +1,6 @@
-import { generateRoutes } from './routes';
+import { readFileSync } from 'fs';
+import { join } from 'path';
+import { ConstraintPatterns, generateRoutes } from './routes';
 
 describe('generateRoutes', () => {
   it('should filter out entry point route', () => {
@@ -20,6 +22,25 @@ describe('generateRoutes', () => {
     `);
   });
 
+  it('should filter out private modules', () => {
+    const files = [
+      'api/foo.rs',
+      'api/_utils.rs',
+      'api/bar/mod.rs',
+      'api/_db/pool.rs',
+    ];
+
+    expect(generateRoutes(files)).toMatchInlineSnapshot(`
+      [
+        {
+          "dest": "/api/foo",
+          "path": "api/foo",
+          "src": "/api/foo",
+        },
+      ]
+    `);
+  });
+
   it('should generate static routes', () => {
     const staticRoutes = ['api/foo.rs', 'api/bar/baz.rs'];
 
@@ -39,6 +60,39 @@ describe('generateRoutes', () => {
     `);
   });
 
+  it('should serve index routes at their directory', () => {
+    const indexRoutes = ['api/users/index.rs', 'api/users/[id].rs'];
+
+    expect(generateRoutes(indexRoutes)).toMatchInlineSnapshot(`
+      [
+        {
+          "dest": "/api/users/index",
+          "path": "api/users/index",
+          "src": "/api/users",
+        },
+        {
+          "dest": "/api/users/[id]?id=$id",
+          "path": "api/users/[id]",
+          "src": "/api/users/(?<id>[^/]+)",
+        },
+      ]
+    `);
+  });
+
+  it('should strip route groups from the URL', () => {
+    const groupedRoutes = ['api/(billing)/invoices.rs'];
+
+    expect(generateRoutes(groupedRoutes)).toMatchInlineSnapshot(`
+      [
+        {
+          "dest": "/api/(billing)/invoices",
+          "path": "api/(billing)/invoices",
+          "src": "/api/invoices",
+        },
+      ]
+    `);
+  });
+
   it('should generate dynamic routes', () => {
     const dynamicRoutes = [
       'api/post/[id].rs',
@@ -61,6 +115,32 @@ describe('generateRoutes', () => {
     `);
   });
 
+  it('should generate constrained dynamic routes', () => {
+    const constrainedRoutes = ['api/users/[id=u64].rs'];
+
+    expect(generateRoutes(constrainedRoutes)).toMatchInlineSnapshot(`
+      [
+        {
+          "dest": "/api/users/[id=u64]?id=$id",
+          "path": "api/users/[id=u64]",
+          "src": "/api/users/(?<id>0*(?:[0-9]{1,19}|[0-0][0-9]{19}|1[0-7][0-9]{18}|18[0-3][0-9]{17}|184[0-3][0-9]{16}|1844[0-5][0-9]{15}|18446[0-6][0-9]{14}|184467[0-3][0-9]{13}|1844674[0-3][0-9]{12}|184467440[0-6][0-9]{10}|1844674407[0-2][0-9]{9}|18446744073[0-6][0-9]{8}|1844674407370[0-8][0-9]{6}|18446744073709[0-4][0-9]{5}|184467440737095[0-4][0-9]{4}|1844674407370955[0-0][0-9]{3}|18446744073709551[0-5][0-9]{2}|184467440737095516[0-0][0-9]{1}|1844674407370955161[0-4]|18446744073709551615))",
+        },
+      ]
+    `);
+  });
+
+  it('should generate the same constraint patterns as the Rust router', () => {
+    // Shared with the `vercel_runtime_router` test suite
+    const patterns = JSON.parse(
+      readFileSync(
+        join(__dirname, '../../test/constraint-patterns.json'),
+        'utf8',
+      ),
+    );
+
+    expect(ConstraintPatterns).toEqual(patterns);
+  });
+
   it('should generate catch-all routes', () => {
     const catchAllRoutes = [
       'api/[...rootAll].rs',
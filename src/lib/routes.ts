@@ -7,6 +7,38 @@ const CatchPriority = {
   OptionalCatchAll: 2,
 };
 
+// Regex matching the decimal numbers up to `max`, with leading zeros, like the Rust router's
+// `at_most`. `max` is a string as `u64::MAX` is not a safe integer.
+function atMost(max: string): string {
+  const alternatives: string[] = [];
+  if (max.length > 1) {
+    alternatives.push(`[0-9]{1,${max.length - 1}}`);
+  }
+  for (let i = 0; i < max.length; i++) {
+    const digit = Number(max[i]);
+    if (digit === 0) {
+      continue;
+    }
+    const rest = max.length - i - 1;
+    alternatives.push(
+      `${max.slice(0, i)}[0-${digit - 1}]${rest > 0 ? `[0-9]{${rest}}` : ''}`,
+    );
+  }
+  alternatives.push(max);
+  return `0*(?:${alternatives.join('|')})`;
+}
+
+// Built-in constraints of `[name=constraint]` segments, generating the same regexes as the
+// Rust router. Constraints registered in code match any segment and are only checked by the
+// Rust router.
+export const ConstraintPatterns: Record<string, string> = {
+  u32: atMost('4294967295'),
+  u64: atMost('18446744073709551615'),
+  i32: `(?:-${atMost('2147483648')}|${atMost('2147483647')})`,
+  i64: `(?:-${atMost('9223372036854775808')}|${atMost('9223372036854775807')})`,
+  uuid: '[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}',
+};
+
 interface Route {
   src: string;
   dest: string;
@@ -23,7 +55,14 @@ interface ParsedRoute {
 
 export function parseRoute(filepath: string): ParsedRoute {
   const route = filepath.endsWith('.rs') ? filepath.slice(0, -3) : filepath;
-  const segments = route.split('/');
+  // `(group)` directories organise routes without changing their URL
+  const segments = route
+    .split('/')
+    .filter((segment) => !(segment.startsWith('(') && segment.endsWith(')')));
+  // `index.rs` serves its directory, e.g. `api/users/index.rs` serves `/api/users`
+  if (segments.length > 1 && segments[segments.length - 1] === 'index') {
+    segments.pop();
+  }
   const result = segments.reduce<{
     catchType: null | number;
     src: string[];
@@ -44,11 +83,13 @@ export function parseRoute(filepath: string): ParsedRoute {
         return acc;
       }
 
-      // Dynamic route
+      // Dynamic route, optionally constrained like `[id=u64]`
       if (segment.startsWith('[') && segment.endsWith(']')) {
-        const parameterName = segment.replace('[', '').replace(']', '');
+        const [parameterName, constraint] = segment.slice(1, -1).split('=');
         acc.catchType = CatchPriority.Dynamic;
-        acc.src.push(`(?<${parameterName}>[^/]+)`);
+        const pattern =
+          (constraint && ConstraintPatterns[constraint]) || '[^/]+';
+        acc.src.push(`(?<${parameterName}>${pattern})`);
         acc.searchParams.set(parameterName, `$${parameterName}`);
         return acc;
       }
@@ -78,8 +119,19 @@ export function parseRoute(filepath: string): ParsedRoute {
   };
 }
 
+// Private modules that routes include with `mod`, like `api/_utils.rs` or `api/users/mod.rs`,
+// are not routes.
+export function isRouteFile(filepath: string): boolean {
+  const segments = filepath.split('/');
+  return (
+    segments[segments.length - 1] !== 'mod.rs' &&
+    !segments.some((segment) => segment.startsWith('_'))
+  );
+}
+
 export function generateRoutes(files: string[]): Route[] {
   const routes = files
+    .filter(isRouteFile)
     .map((file) => {
       return parseRoute(file);
     })
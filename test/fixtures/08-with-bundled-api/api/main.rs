@@ -0,0 +1,22 @@
+use vercel_runtime::{bundled_api, run, Body, Error, Request, Response};
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    // `main <path>` serves a single request and prints the response, used by the macro's tests
+    if let Some(path) = std::env::args().nth(1) {
+        let mut req = Request::new(Body::Empty);
+        *req.uri_mut() = path.parse()?;
+        let res = handler(req).await?;
+        let body = match res.body() {
+            Body::Text(text) => text.as_str(),
+            _ => "",
+        };
+        println!("{} {}", res.status().as_u16(), body);
+        return Ok(());
+    }
+
+    run(handler).await
+}
+
+#[bundled_api]
+pub async fn handler(req: Request) -> Result<Response<Body>, Error> {}
@@ -0,0 +1,3 @@
+fn main() {
+    vercel_runtime_router::track_routes("api");
+}